@@ -3,37 +3,70 @@
 in vec3 v_position;
 in vec3 v_normal;
 in vec2 v_texcoord;
-in vec4 v_offset_position;
+in vec2 v_light;
+in float v_occlusion;
+in vec3 v_color;
 
 out vec4 f_color;
 
 uniform sampler2D tex;
 uniform vec3 camera_position;
+uniform vec3 sun_direction;
+uniform float sky_light;
+uniform vec3 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+
+// Cascaded shadow maps, nearest first. A strength of 0 means shadows are off
+uniform sampler2DShadow shadow_map_0;
+uniform sampler2DShadow shadow_map_1;
+uniform sampler2DShadow shadow_map_2;
+uniform mat4 shadow_matrix_0;
+uniform mat4 shadow_matrix_1;
+uniform mat4 shadow_matrix_2;
+uniform vec3 cascade_radius;
+uniform float shadow_resolution;
+uniform float shadow_strength;
+
+// Fraction of light reaching a point in one cascade, averaged over a 3x3 block of texels
+float sample_shadow(sampler2DShadow shadow_map, mat4 shadow_matrix, vec3 position) {
+    vec3 coords = (shadow_matrix * vec4(position, 1.0)).xyz * 0.5 + 0.5;
+    float texel = 1.0 / shadow_resolution;
+    float lit = 0.0;
+
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadow_map, vec3(coords.xy + vec2(x, y) * texel, coords.z));
+        }
+    }
+
+    return lit / 9.0;
+}
 
-float mod289(float x){return x - floor(x * (1.0 / 289.0)) * 289.0;}
-vec4 mod289(vec4 x){return x - floor(x * (1.0 / 289.0)) * 289.0;}
-vec4 perm(vec4 x){return mod289(((x * 34.0) + 1.0) * x);}
-
-float noise(vec3 p){
-    vec3 a = floor(p);
-    vec3 d = p - a;
-    d = d * d * (3.0 - 2.0 * d);
+float shadow(vec3 normal, vec3 light_direction) {
+    float view_distance = length(v_position - camera_position);
 
-    vec4 b = a.xxyy + vec4(0.0, 1.0, 0.0, 1.0);
-    vec4 k1 = perm(b.xyxy);
-    vec4 k2 = perm(k1.xyxy + b.zzww);
+    if (shadow_strength <= 0.0 || view_distance > cascade_radius.z) {
+        return 1.0;
+    }
 
-    vec4 c = k2 + a.zzzz;
-    vec4 k3 = perm(c);
-    vec4 k4 = perm(c + 1.0);
+    // Push the lookup off the surface by about a texel of the cascade in use, so faces don't
+    // shadow themselves
+    int cascade = view_distance < cascade_radius.x ? 0 : (view_distance < cascade_radius.y ? 1 : 2);
+    float texel_size = 2.0 * cascade_radius[cascade] / shadow_resolution;
+    vec3 position = v_position + (normal * 1.5 + light_direction) * texel_size;
 
-    vec4 o1 = fract(k3 * (1.0 / 41.0));
-    vec4 o2 = fract(k4 * (1.0 / 41.0));
+    float lit;
 
-    vec4 o3 = o2 * d.z + o1 * (1.0 - d.z);
-    vec2 o4 = o3.yw * d.x + o3.xz * (1.0 - d.x);
+    if (cascade == 0) {
+        lit = sample_shadow(shadow_map_0, shadow_matrix_0, position);
+    } else if (cascade == 1) {
+        lit = sample_shadow(shadow_map_1, shadow_matrix_1, position);
+    } else {
+        lit = sample_shadow(shadow_map_2, shadow_matrix_2, position);
+    }
 
-    return o4.y * d.y + o4.x * (1.0 - d.y);
+    return mix(1.0, lit, shadow_strength);
 }
 
 void main() {
@@ -41,16 +74,32 @@ void main() {
     vec3 light_color = vec3(0.8, 0.95, 1.0);
     vec3 ambient = ambient_strength * light_color; // Not yet implemented
 
-    vec3 light_direction = vec3(-0.2, 0.8, 0.1);
+    // The moon lights the world from the opposite side at night
+    vec3 light_direction = sun_direction.y > 0.0 ? sun_direction : -sun_direction;
+
+    vec3 normal = normalize(v_normal);
+    light_direction = normalize(light_direction);
+
+    // Faces turned away from the light are already as dark as shadow makes them
+    float facing = dot(normal, light_direction);
+    float lum = max(facing > 0.0 ? facing * shadow(normal, light_direction) : facing, 0.25);
+
+    // Sky and block light levels arrive as 0-1, each level below 15 is 20% darker
+    float sky = pow(0.8, (1.0 - v_light.x) * 15.0) * sky_light;
+    float block_light = pow(0.8, (1.0 - v_light.y) * 15.0);
+    float voxel_light = max(max(sky, block_light), 0.03);
+
+    // Fully enclosed corners keep 40% of their light
+    float occlusion = 0.4 + 0.6 * v_occlusion;
 
-    float lum = max(dot(normalize(v_normal), normalize(light_direction)), 0.25);
-    vec3 color_r = (0.3 + 0.7 * lum) * vec3(1.0, 1.0, 1.0) * light_color;
+    vec3 color_r = (0.3 + 0.7 * lum) * voxel_light * occlusion * vec3(1.0, 1.0, 1.0) * light_color;
 
-    // Add a fog effect depending on the distance from the camera
-    float fog_factor = 1.0;//(1.0 - clamp(length(v_offset_position.xyz - camera_position) / 100.0, 0.0, 1.0));
+    // Per-block colour baked into the mesh, white unless colour jitter is on
+    vec4 lit_color = texture(tex, v_texcoord) * vec4(color_r * v_color, 1.0);
 
-    // Add a little bit of noise to the color
-    float noise_factor = noise(v_position * 500000.0) / 4.0 + 0.75;
+    // Fade into the horizon colour with distance. Chunks load in columns, so only the horizontal
+    // distance counts and the fog follows the edge of the loaded area
+    float fog_factor = 1.0 - smoothstep(fog_start, fog_end, length(v_position.xz - camera_position.xz));
 
-    f_color = (texture(tex, v_texcoord) * vec4(color_r, 1.0) * fog_factor) * noise_factor + (vec4(0.0, 0.0, 0.0, 1.0) * (1.0 - fog_factor));
+    f_color = vec4(mix(fog_color, lit_color.rgb, fog_factor), lit_color.a);
 }
\ No newline at end of file
@@ -3,11 +3,16 @@
 in vec3 normal;
 in vec3 position;
 in vec2 tex_coords;
+in vec2 light;
+in float occlusion;
+in vec3 color;
 
 out vec3 v_normal;
 out vec3 v_position;
-out vec4 v_offset_position;
 out vec2 v_texcoord;
+out vec2 v_light;
+out float v_occlusion;
+out vec3 v_color;
 
 uniform mat4 perspective;
 uniform mat4 view;
@@ -16,10 +21,13 @@ uniform mat4 model;
 
 void main() {
     v_normal = normal;
-    v_position = position;
+    // World space, which fog distances are measured in
+    v_position = (model * vec4(position, 1.0)).xyz;
     v_texcoord = tex_coords;
+    v_light = light;
+    v_occlusion = occlusion;
+    v_color = color;
 
     mat4 vm = view * model;
-    v_offset_position = perspective * vm * vec4(position, 1.0);
     gl_Position = perspective * vm * vec4(position, 1.0);
 }
\ No newline at end of file
@@ -2,14 +2,32 @@
 
 in vec3 v_position;
 in vec3 v_normal;
-in vec2 v_texcoord;
-in vec4 v_offset_position;
 
 out vec4 f_color;
 
-uniform sampler2D tex;
 uniform vec3 camera_position;
+uniform float sky_light;
+uniform vec3 fog_color;
+uniform float fade_start;
+uniform float fade_end;
 
 void main() {
-    f_color = vec4(1.0, 1.0, 1.0, 1.0);
-}
\ No newline at end of file
+    // Flat shading by face direction: tops are brightest, undersides darkest
+    float shade = 0.85;
+
+    if (v_normal.y > 0.5) {
+        shade = 1.0;
+    } else if (v_normal.y < -0.5) {
+        shade = 0.7;
+    }
+
+    vec3 color = vec3(shade * sky_light);
+
+    // Fade out towards the edge of the layer so it doesn't end in a hard line
+    float horizontal_distance = length(v_position.xz - camera_position.xz);
+    float fade = 1.0 - smoothstep(fade_start, fade_end, horizontal_distance);
+
+    color = mix(fog_color, color, fade);
+
+    f_color = vec4(color, 0.8 * fade);
+}
@@ -2,12 +2,9 @@
 
 in vec3 normal;
 in vec3 position;
-in vec2 tex_coords;
 
 out vec3 v_normal;
 out vec3 v_position;
-out vec4 v_offset_position;
-out vec2 v_texcoord;
 
 uniform mat4 perspective;
 uniform mat4 view;
@@ -15,11 +12,10 @@ uniform mat4 model;
 
 
 void main() {
+    vec4 world_position = model * vec4(position, 1.0);
+
     v_normal = normal;
-    v_position = position;
-    v_texcoord = tex_coords;
+    v_position = world_position.xyz;
 
-    mat4 vm = view * model;
-    v_offset_position = perspective * vm * vec4(position, 1.0);
-    gl_Position = perspective * vm * vec4(position, 1.0);
-}
\ No newline at end of file
+    gl_Position = perspective * view * world_position;
+}
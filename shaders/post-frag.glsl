@@ -0,0 +1,89 @@
+#version 140
+
+in vec2 v_texcoord;
+
+out vec4 f_color;
+
+uniform sampler2D scene;
+// Which effect this pass runs
+uniform int effect;
+// Size of one pixel of the scene in texture coordinates
+uniform vec2 texel;
+uniform float exposure;
+// 1 while the camera is in water
+uniform float underwater;
+
+// Matches effect_id in postprocess.rs
+const int TONEMAP = 0;
+const int GAMMA = 1;
+const int FXAA = 2;
+const int UNDERWATER = 3;
+const int VIGNETTE = 4;
+
+// Fitted ACES filmic curve
+vec3 tonemap(vec3 color) {
+    color *= exposure;
+
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 gamma(vec3 color) {
+    return pow(max(color, 0.0), vec3(1.0 / 2.2));
+}
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// Blur along edges found from the contrast between neighbouring pixels. Works best after gamma
+vec3 fxaa(vec2 uv) {
+    vec3 north_west = texture(scene, uv + vec2(-1.0, -1.0) * texel).rgb;
+    vec3 north_east = texture(scene, uv + vec2(1.0, -1.0) * texel).rgb;
+    vec3 south_west = texture(scene, uv + vec2(-1.0, 1.0) * texel).rgb;
+    vec3 south_east = texture(scene, uv + vec2(1.0, 1.0) * texel).rgb;
+    vec3 middle = texture(scene, uv).rgb;
+
+    float luma_nw = luma(north_west);
+    float luma_ne = luma(north_east);
+    float luma_sw = luma(south_west);
+    float luma_se = luma(south_east);
+    float luma_m = luma(middle);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    vec2 direction = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+
+    // Short, faint edges get blurred less
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * (1.0 / 8.0), 1.0 / 128.0);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, -8.0, 8.0) * texel;
+
+    vec3 near = 0.5 * (texture(scene, uv + direction * (1.0 / 3.0 - 0.5)).rgb + texture(scene, uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 far = near * 0.5 + 0.25 * (texture(scene, uv - direction * 0.5).rgb + texture(scene, uv + direction * 0.5).rgb);
+
+    // Reaching too far picks up colours from across the edge
+    float luma_far = luma(far);
+
+    return (luma_far < luma_min || luma_far > luma_max) ? near : far;
+}
+
+void main() {
+    vec3 color = texture(scene, v_texcoord).rgb;
+
+    if (effect == TONEMAP) {
+        color = tonemap(color);
+    } else if (effect == GAMMA) {
+        color = gamma(color);
+    } else if (effect == FXAA) {
+        color = fxaa(v_texcoord);
+    } else if (effect == UNDERWATER) {
+        vec3 tinted = color * vec3(0.25, 0.55, 0.8) + vec3(0.0, 0.03, 0.06);
+        color = mix(color, tinted, underwater * 0.8);
+    } else if (effect == VIGNETTE) {
+        float edge = length(v_texcoord - 0.5) * 1.414;
+        color *= 1.0 - 0.35 * smoothstep(0.5, 1.0, edge);
+    }
+
+    f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 140
+
+out vec2 v_texcoord;
+
+
+void main() {
+    // One triangle that covers the whole screen, no vertex buffer needed
+    vec2 position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+
+    v_texcoord = position * 0.5 + 0.5;
+
+    gl_Position = vec4(position, 0.0, 1.0);
+}
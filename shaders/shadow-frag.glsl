@@ -0,0 +1,5 @@
+#version 140
+
+// Only depth is written
+void main() {
+}
@@ -0,0 +1,11 @@
+#version 140
+
+in vec3 position;
+
+uniform mat4 shadow_matrix;
+
+
+void main() {
+    // Chunk meshes are already in world space
+    gl_Position = shadow_matrix * vec4(position, 1.0);
+}
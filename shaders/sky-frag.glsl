@@ -7,38 +7,27 @@ in vec4 v_offset_position;
 
 out vec4 f_color;
 
-uniform sampler2D tex;
 uniform vec3 camera_position;
+uniform vec3 sun_direction;
+uniform vec3 sky_color;
+uniform vec3 fog_color;
 
-float mod289(float x){return x - floor(x * (1.0 / 289.0)) * 289.0;}
-vec4 mod289(vec4 x){return x - floor(x * (1.0 / 289.0)) * 289.0;}
-vec4 perm(vec4 x){return mod289(((x * 34.0) + 1.0) * x);}
-
-float noise(vec3 p){
-    vec3 a = floor(p);
-    vec3 d = p - a;
-    d = d * d * (3.0 - 2.0 * d);
-
-    vec4 b = a.xxyy + vec4(0.0, 1.0, 0.0, 1.0);
-    vec4 k1 = perm(b.xyxy);
-    vec4 k2 = perm(k1.xyxy + b.zzww);
-
-    vec4 c = k2 + a.zzzz;
-    vec4 k3 = perm(c);
-    vec4 k4 = perm(c + 1.0);
-
-    vec4 o1 = fract(k3 * (1.0 / 41.0));
-    vec4 o2 = fract(k4 * (1.0 / 41.0));
+void main() {
+    vec3 direction = normalize(v_position - camera_position);
 
-    vec4 o3 = o2 * d.z + o1 * (1.0 - d.z);
-    vec2 o4 = o3.yw * d.x + o3.xz * (1.0 - d.x);
+    // Fade from the horizon colour up to the sky colour overhead
+    float height = clamp(direction.y, 0.0, 1.0);
+    vec3 color = mix(fog_color, sky_color, sqrt(height));
 
-    return o4.y * d.y + o4.x * (1.0 - d.y);
-}
+    // The sun with a soft glow around it, and the moon opposite
+    float sun_angle = dot(direction, sun_direction);
+    float sun = smoothstep(0.9985, 0.999, sun_angle);
+    float glow = pow(max(sun_angle, 0.0), 64.0) * 0.35;
+    float moon = smoothstep(0.9992, 0.9995, -sun_angle);
 
-void main() {
-    // Add a little bit of noise to the color
-    float noise_factor = noise(v_position * 500000.0) / 4.0 + 0.75;
+    color += vec3(1.0, 0.85, 0.6) * glow;
+    color = mix(color, vec3(1.0, 0.97, 0.85), sun);
+    color = mix(color, vec3(0.8, 0.85, 0.95), moon);
 
-    f_color = texture(tex, v_texcoord) * noise_factor;
+    f_color = vec4(color, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,358 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[cfg(not(feature = "hot-reload"))]
+use include_dir::{include_dir, Dir};
+use serde::Deserialize;
+
+use crate::settings;
+
+// Every asset lives under one of these directories, and is addressed by its path inside them, e.g. "shaders/basic-frag.glsl"
+#[cfg_attr(not(feature = "hot-reload"), allow(dead_code))]
+pub const ASSET_DIRECTORIES: [&str; 4] = ["shaders", "textures", "models", "blocks"];
+
+// Describes a resource pack, found at the root of its directory or zip
+pub const MANIFEST_FILE: &str = "pack.json";
+
+#[cfg(not(feature = "hot-reload"))]
+static EMBEDDED: [(&str, Dir<'_>); 4] = [
+    ("shaders", include_dir!("shaders")),
+    ("textures", include_dir!("textures")),
+    ("models", include_dir!("models")),
+    ("blocks", include_dir!("blocks"))
+];
+
+#[derive(Clone, Deserialize)]
+pub struct PackManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // Packs with a higher priority override those with a lower one
+    #[serde(default)]
+    pub priority: i32
+}
+
+enum PackSource {
+    // Files are read from disk every time, so edits show up on the next load
+    Directory(PathBuf),
+    // Zips are read into memory once
+    Zip(HashMap<String, Vec<u8>>)
+}
+
+pub struct ResourcePack {
+    pub manifest: PackManifest,
+    source: PackSource
+}
+
+impl ResourcePack {
+    pub fn open(path: &Path) -> Result<ResourcePack, String> {
+        let source = if path.is_dir() {
+            PackSource::Directory(path.to_path_buf())
+        } else {
+            PackSource::Zip(read_zip(path)?)
+        };
+
+        let mut pack = ResourcePack {
+            manifest: PackManifest { name: String::new(), description: String::new(), priority: 0 },
+            source: source
+        };
+
+        let manifest = pack.read(MANIFEST_FILE).ok_or_else(|| format!("{}: no {}", path.display(), MANIFEST_FILE))?;
+        pack.manifest = serde_json::from_slice(&manifest).map_err(|e| format!("{}: {}: {}", path.display(), MANIFEST_FILE, e))?;
+
+        return Ok(pack);
+    }
+
+    fn read(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        match &self.source {
+            PackSource::Directory(root) => fs::read(root.join(path)).ok().map(Cow::Owned),
+            PackSource::Zip(files) => files.get(path).map(|contents| Cow::Owned(contents.clone()))
+        }
+    }
+
+    fn list(&self, directory: &str) -> Vec<String> {
+        match &self.source {
+            PackSource::Directory(root) => list_directory(root, directory),
+            PackSource::Zip(files) => {
+                files.keys()
+                    .filter(|name| name.strip_prefix(directory).and_then(|rest| rest.strip_prefix('/')).is_some_and(|file| !file.contains('/')))
+                    .cloned()
+                    .collect()
+            }
+        }
+    }
+}
+
+fn read_zip(path: &Path) -> Result<HashMap<String, Vec<u8>>, String> {
+    let file = fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut files = HashMap::new();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        if entry.is_dir() {
+            continue;
+        }
+
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).map_err(|e| format!("{}: {}: {}", path.display(), entry.name(), e))?;
+
+        files.insert(entry.name().to_string(), contents);
+    }
+
+    return Ok(files);
+}
+
+// Files directly inside `root/directory`, as asset paths
+fn list_directory(root: &Path, directory: &str) -> Vec<String> {
+    let entries = match fs::read_dir(root.join(directory)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new()
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| format!("{}/{}", directory, entry.file_name().to_string_lossy()))
+        .collect()
+}
+
+// Find every pack in the pack directory and sort them from the highest priority to the lowest.
+// Packs named in `order` come first, in that order, and the rest follow by their manifest priority
+pub fn find_packs(pack_settings: &settings::ResourcePackSettings) -> Vec<ResourcePack> {
+    let entries = match fs::read_dir(&pack_settings.directory) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new()
+    };
+
+    let mut packs = Vec::new();
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let is_zip = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+
+        if !(path.is_dir() || is_zip) {
+            continue;
+        }
+
+        match ResourcePack::open(&path) {
+            Ok(pack) => packs.push(pack),
+            Err(error) => eprintln!("Skipping resource pack: {}", error)
+        }
+    }
+
+    sort_packs(&mut packs, &pack_settings.order);
+
+    return packs;
+}
+
+fn sort_packs(packs: &mut [ResourcePack], order: &[String]) {
+    packs.sort_by_key(|pack| {
+        let listed = order.iter().position(|name| *name == pack.manifest.name);
+
+        // Listed packs sort by their place in the list, ahead of all unlisted ones
+        (listed.unwrap_or(usize::MAX), -pack.manifest.priority, pack.manifest.name.clone())
+    });
+}
+
+// Reads asset files through the resource packs, falling back to the built in assets.
+// Those are baked into the binary for release builds, or read straight from the source tree with `hot-reload`
+pub struct Assets {
+    #[cfg(feature = "hot-reload")]
+    root: PathBuf,
+    // Highest priority first
+    packs: Vec<ResourcePack>
+}
+
+impl Assets {
+    pub fn new(packs: Vec<ResourcePack>) -> Assets {
+        Assets {
+            #[cfg(feature = "hot-reload")]
+            root: PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+            packs: packs
+        }
+    }
+
+    // Directories asset files are read from, the built in assets first and then every directory pack
+    #[cfg(feature = "hot-reload")]
+    pub fn get_roots(&self) -> Vec<PathBuf> {
+        let mut roots = vec![self.root.clone()];
+
+        for pack in self.packs.iter() {
+            if let PackSource::Directory(root) = &pack.source {
+                roots.push(root.clone());
+            }
+        }
+
+        return roots;
+    }
+
+    pub fn read(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        for pack in self.packs.iter() {
+            if let Some(contents) = pack.read(path) {
+                return Some(contents);
+            }
+        }
+
+        self.read_built_in(path)
+    }
+
+    // Paths of the files directly inside an asset directory across every pack, sorted so load order doesn't depend on the platform
+    pub fn list(&self, directory: &str) -> Vec<String> {
+        let mut paths = self.list_built_in(directory);
+
+        for pack in self.packs.iter() {
+            paths.extend(pack.list(directory));
+        }
+
+        paths.sort();
+        paths.dedup();
+
+        return paths;
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    fn read_built_in(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        let (directory, file) = path.split_once('/')?;
+
+        EMBEDDED.iter()
+            .find(|(name, _)| *name == directory)
+            .and_then(|(_, dir)| dir.get_file(file))
+            .map(|file| Cow::Borrowed(file.contents()))
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn read_built_in(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        fs::read(self.root.join(path)).ok().map(Cow::Owned)
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    fn list_built_in(&self, directory: &str) -> Vec<String> {
+        EMBEDDED.iter()
+            .filter(|(name, _)| *name == directory)
+            .flat_map(|(_, dir)| dir.files())
+            .map(|file| format!("{}/{}", directory, file.path().display()))
+            .collect()
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn list_built_in(&self, directory: &str) -> Vec<String> {
+        list_directory(&self.root, directory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // A scratch directory for one test, emptied first
+    fn scratch_directory(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("block_game_{}_{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        return path;
+    }
+
+    fn write_directory_pack(root: &Path, name: &str, priority: i32, files: &[(&str, &str)]) {
+        let pack = root.join(name);
+
+        fs::create_dir_all(&pack).unwrap();
+        fs::write(pack.join(MANIFEST_FILE), format!(r#"{{ "name": "{}", "priority": {} }}"#, name, priority)).unwrap();
+
+        for (path, contents) in files {
+            let file = pack.join(path);
+
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, contents).unwrap();
+        }
+    }
+
+    fn write_zip_pack(root: &Path, name: &str, priority: i32, files: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(root.join(format!("{}.zip", name))).unwrap());
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+        zip.start_file(MANIFEST_FILE, options).unwrap();
+        write!(zip, r#"{{ "name": "{}", "priority": {} }}"#, name, priority).unwrap();
+
+        for (path, contents) in files {
+            zip.start_file(*path, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+
+        zip.finish().unwrap();
+    }
+
+    fn pack_settings(directory: &Path, order: &[&str]) -> settings::ResourcePackSettings {
+        settings::ResourcePackSettings {
+            directory: directory.to_string_lossy().to_string(),
+            order: order.iter().map(|name| name.to_string()).collect()
+        }
+    }
+
+    fn read_string(assets: &Assets, path: &str) -> String {
+        String::from_utf8(assets.read(path).unwrap().into_owned()).unwrap()
+    }
+
+    #[test]
+    fn packs_override_built_in_files_by_path() {
+        let root = scratch_directory("override");
+        write_directory_pack(&root, "bright", 0, &[("shaders/basic-frag.glsl", "bright")]);
+
+        let assets = Assets::new(find_packs(&pack_settings(&root, &[])));
+
+        assert_eq!(read_string(&assets, "shaders/basic-frag.glsl"), "bright");
+        // Files the pack doesn't have still come from the built in assets
+        assert!(assets.read("shaders/basic-vert.glsl").is_some());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn higher_priority_wins_unless_the_order_says_otherwise() {
+        let root = scratch_directory("priority");
+        write_directory_pack(&root, "low", 1, &[("blocks/extra.json", "low")]);
+        write_zip_pack(&root, "high", 5, &[("blocks/extra.json", "high"), ("blocks/zipped.json", "zipped")]);
+
+        let assets = Assets::new(find_packs(&pack_settings(&root, &[])));
+        assert_eq!(read_string(&assets, "blocks/extra.json"), "high");
+        assert_eq!(read_string(&assets, "blocks/zipped.json"), "zipped");
+
+        let assets = Assets::new(find_packs(&pack_settings(&root, &["low"])));
+        assert_eq!(read_string(&assets, "blocks/extra.json"), "low");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn listing_merges_every_layer() {
+        let root = scratch_directory("listing");
+        write_zip_pack(&root, "extra", 0, &[("blocks/stone.json", "{}"), ("blocks/nested/ignored.json", "{}")]);
+
+        let assets = Assets::new(find_packs(&pack_settings(&root, &[])));
+        let blocks = assets.list("blocks");
+
+        assert!(blocks.contains(&"blocks/stone.json".to_string()));
+        assert!(blocks.contains(&"blocks/grass.json".to_string()));
+        assert!(!blocks.iter().any(|path| path.contains("nested")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn broken_packs_are_skipped() {
+        let root = scratch_directory("broken");
+        fs::create_dir_all(root.join("no_manifest")).unwrap();
+        fs::write(root.join("not_a_zip.zip"), "garbage").unwrap();
+
+        assert!(find_packs(&pack_settings(&root, &[])).is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use noise::{NoiseFn, Perlin};
+
+// Blocks across one patch of climate noise. Large, so biomes change slowly
+const CLIMATE_SCALE: f64 = 400.0;
+
+// Temperature and humidity at each column, from 0 to 1
+pub struct Climate {
+    temperature: Perlin,
+    humidity: Perlin
+}
+
+impl Climate {
+    pub fn new(seed: u32) -> Climate {
+        Climate {
+            // Their own seeds, so climate doesn't line up with the terrain or each other
+            temperature: Perlin::new(seed.wrapping_add(2)),
+            humidity: Perlin::new(seed.wrapping_add(3))
+        }
+    }
+
+    pub fn get(&self, x: i32, z: i32) -> (f32, f32) {
+        let point = [x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE];
+
+        let temperature = (self.temperature.get(point) * 0.5 + 0.5).clamp(0.0, 1.0);
+        let humidity = (self.humidity.get(point) * 0.5 + 0.5).clamp(0.0, 1.0);
+
+        return (temperature as f32, humidity as f32);
+    }
+}
+
+// Tint colours by climate, read from `textures/colormap.png`. Temperature runs left to right from
+// cold to hot, humidity bottom to top from dry to wet
+#[derive(Clone)]
+pub struct Colormap {
+    width: u32,
+    height: u32,
+    // Linear colour, the shaders multiply it with textures that are already linear
+    colors: Vec<[f32; 3]>
+}
+
+impl Colormap {
+    pub fn new(image: &image::RgbaImage) -> Colormap {
+        let colors = image.pixels().map(|pixel| {
+            [srgb_to_linear(pixel[0]), srgb_to_linear(pixel[1]), srgb_to_linear(pixel[2])]
+        }).collect();
+
+        Colormap {
+            width: image.width(),
+            height: image.height(),
+            colors: colors
+        }
+    }
+
+    pub fn get_color(&self, temperature: f32, humidity: f32) -> [f32; 3] {
+        if self.colors.is_empty() {
+            return [1.0, 1.0, 1.0];
+        }
+
+        let x = (temperature.clamp(0.0, 1.0) * (self.width - 1) as f32).round() as u32;
+        let y = ((1.0 - humidity.clamp(0.0, 1.0)) * (self.height - 1) as f32).round() as u32;
+
+        return self.colors[(y * self.width + x) as usize];
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colormap_is_read_by_temperature_and_humidity() {
+        let mut image = image::RgbaImage::new(2, 2);
+
+        // Top row is wet, left column is cold
+        image.put_pixel(0, 0, image::Rgba([0, 0, 255, 255]));
+        image.put_pixel(1, 0, image::Rgba([0, 255, 0, 255]));
+        image.put_pixel(0, 1, image::Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 1, image::Rgba([255, 0, 0, 255]));
+
+        let colormap = Colormap::new(&image);
+
+        assert_eq!(colormap.get_color(0.0, 1.0), [0.0, 0.0, 1.0]);
+        assert_eq!(colormap.get_color(1.0, 1.0), [0.0, 1.0, 0.0]);
+        assert_eq!(colormap.get_color(0.0, 0.0), [1.0, 1.0, 1.0]);
+        assert_eq!(colormap.get_color(2.0, -1.0), [1.0, 0.0, 0.0]);
+    }
+}
@@ -1,9 +1,114 @@
 use std::collections::HashMap;
+use std::fmt;
 
+use serde::Deserialize;
+
+use crate::assets;
+use crate::chunk;
 use crate::cube;
 
+// Size of the texture atlas and of one tile in it, in pixels
+pub const ATLAS_SIZE: f32 = 256.0;
+pub const TILE_SIZE: f32 = 16.0;
+
+#[derive(Debug)]
+pub enum BlockError {
+    NotFound(String),
+    Parse { file: String, source: serde_json::Error },
+    MissingTexture { file: String, face: String },
+    UnknownFace { file: String, face: String }
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::NotFound(file) => write!(f, "{}: file not found", file),
+            BlockError::Parse { file, source } => write!(f, "{}: {}", file, source),
+            BlockError::MissingTexture { file, face } => write!(f, "{}: no texture for the {} face", file, face),
+            BlockError::UnknownFace { file, face } => write!(f, "{}: '{}' isn't a face", file, face)
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+// A block as written in `blocks/*.json`
+#[derive(Deserialize)]
+pub struct BlockDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub transparent: bool,
+    // Block light the block gives off, 0 to 15
+    #[serde(default)]
+    pub light: u8,
+    // Whether the player collides with it. Liquids can be moved through
+    #[serde(default = "default_solid")]
+    pub solid: bool,
+    pub textures: FaceTextures,
+    // Faces coloured by the biome, named like the texture entries. Their tiles should be grayscale
+    #[serde(default)]
+    pub tint: Vec<String>
+}
+
+fn default_solid() -> bool {
+    return true;
+}
+
+// Atlas position of each face's tile in pixels. A face without its own entry uses `side` (for the four walls), then `all`
+#[derive(Deserialize)]
+pub struct FaceTextures {
+    all: Option<(u32, u32)>,
+    side: Option<(u32, u32)>,
+    top: Option<(u32, u32)>,
+    bottom: Option<(u32, u32)>,
+    front: Option<(u32, u32)>,
+    back: Option<(u32, u32)>,
+    left: Option<(u32, u32)>,
+    right: Option<(u32, u32)>
+}
+
+impl FaceTextures {
+    fn get(&self, face: &cube::Faces) -> Option<(u32, u32)> {
+        let (own, side) = match face {
+            cube::Faces::Top => (self.top, None),
+            cube::Faces::Bottom => (self.bottom, None),
+            cube::Faces::Front => (self.front, self.side),
+            cube::Faces::Back => (self.back, self.side),
+            cube::Faces::Left => (self.left, self.side),
+            cube::Faces::Right => (self.right, self.side)
+        };
+
+        own.or(side).or(self.all)
+    }
+}
+
+// Faces a name from a block file refers to
+fn named_faces(name: &str) -> Option<Vec<cube::Faces>> {
+    let walls = vec![cube::Faces::Front, cube::Faces::Back, cube::Faces::Left, cube::Faces::Right];
+
+    let faces = match name {
+        "all" => [walls, vec![cube::Faces::Top, cube::Faces::Bottom]].concat(),
+        "side" => walls,
+        "top" => vec![cube::Faces::Top],
+        "bottom" => vec![cube::Faces::Bottom],
+        "front" => vec![cube::Faces::Front],
+        "back" => vec![cube::Faces::Back],
+        "left" => vec![cube::Faces::Left],
+        "right" => vec![cube::Faces::Right],
+        _ => return None
+    };
+
+    return Some(faces);
+}
+
 pub struct Block {
     pub cubes: Vec<cube::Cube>,
+    pub transparent: bool,
+    pub light: u8,
+    pub solid: bool,
+    // Faces that take the biome's colour
+    pub tinted: Vec<cube::Faces>,
+    #[allow(dead_code)]
     name: String
 }
 
@@ -22,6 +127,9 @@ pub struct Block {
 pub struct BlockBuilder {
     cubes: Option<Vec<cube::Cube>>,
     transparent: bool,
+    light: u8,
+    solid: bool,
+    tinted: Vec<cube::Faces>,
     name: String
 }
 
@@ -30,6 +138,9 @@ impl BlockBuilder {
         BlockBuilder {
             cubes: None,
             transparent: false,
+            light: 0,
+            solid: true,
+            tinted: Vec::new(),
             name: "none".to_string()
         }
     }
@@ -46,6 +157,24 @@ impl BlockBuilder {
         self
     }
 
+    pub fn set_light(mut self, light: u8) -> Self {
+        self.light = light.min(chunk::MAX_LIGHT);
+
+        self
+    }
+
+    pub fn set_solid(mut self, solid: bool) -> Self {
+        self.solid = solid;
+
+        self
+    }
+
+    pub fn set_tinted(mut self, tinted: Vec<cube::Faces>) -> Self {
+        self.tinted = tinted;
+
+        self
+    }
+
     pub fn add_cube(mut self, cube: cube::Cube) -> Self {
         if self.cubes.is_none() {
             self.cubes = Some(Vec::new());
@@ -56,9 +185,50 @@ impl BlockBuilder {
         self
     }
 
+    // Start a builder from a block file, with one textured cube
+    pub fn from_definition(file: &str, definition: &BlockDefinition) -> Result<BlockBuilder, BlockError> {
+        let mut block_cube = cube::Cube::new();
+
+        for (face_type, face) in block_cube.faces.iter_mut() {
+            let (x, y) = match definition.textures.get(face_type) {
+                Some(tile) => tile,
+                None => return Err(BlockError::MissingTexture { file: file.to_string(), face: format!("{:?}", face_type).to_lowercase() })
+            };
+
+            face.set_face_texture_offset(
+                (TILE_SIZE / ATLAS_SIZE, TILE_SIZE / ATLAS_SIZE),
+                (x as f32 / ATLAS_SIZE, 1.0 - y as f32 / ATLAS_SIZE)
+            );
+        }
+
+        let mut tinted = Vec::new();
+
+        for name in definition.tint.iter() {
+            match named_faces(name) {
+                Some(faces) => tinted.extend(faces),
+                None => return Err(BlockError::UnknownFace { file: file.to_string(), face: name.clone() })
+            }
+        }
+
+        let builder = BlockBuilder::new()
+            .set_name(&definition.name)
+            .set_transparent(definition.transparent)
+            .set_light(definition.light)
+            .set_solid(definition.solid)
+            .set_tinted(tinted)
+            .add_cube(block_cube);
+
+        return Ok(builder);
+    }
+
+    #[allow(clippy::single_match)]
     pub fn build(&self, add_to: Option<&mut HashMap<String, Block>>) {
         let new_block = Block {
             cubes: self.cubes.as_ref().unwrap().clone(),
+            transparent: self.transparent,
+            light: self.light,
+            solid: self.solid,
+            tinted: self.tinted.clone(),
             name: self.name.to_string(),
         };
 
@@ -69,4 +239,69 @@ impl BlockBuilder {
             None => {}
         }
     }
-}
\ No newline at end of file
+}
+
+// Build every block in the `blocks` asset directory. Nothing is returned unless all of them load
+pub fn load_blocks(assets: &assets::Assets) -> Result<HashMap<String, Block>, BlockError> {
+    let mut blocks = HashMap::new();
+
+    for path in assets.list("blocks") {
+        if !path.ends_with(".json") {
+            continue;
+        }
+
+        let contents = assets.read(&path).ok_or_else(|| BlockError::NotFound(path.clone()))?;
+        let definition: BlockDefinition = serde_json::from_slice(&contents)
+            .map_err(|error| BlockError::Parse { file: path.clone(), source: error })?;
+
+        BlockBuilder::from_definition(&path, &definition)?.build(Some(&mut blocks));
+    }
+
+    return Ok(blocks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_blocks_load() {
+        let blocks = load_blocks(&assets::Assets::new(Vec::new())).unwrap();
+
+        assert!(blocks.contains_key("grass"));
+        assert!(blocks.contains_key("dirt"));
+    }
+
+    #[test]
+    fn faces_fall_back_to_side_then_all() {
+        let definition: BlockDefinition = serde_json::from_str(r#"{ "name": "log", "textures": { "top": [16, 0], "side": [32, 0], "all": [48, 0] } }"#).unwrap();
+
+        assert_eq!(definition.textures.get(&cube::Faces::Top), Some((16, 0)));
+        assert_eq!(definition.textures.get(&cube::Faces::Left), Some((32, 0)));
+        assert_eq!(definition.textures.get(&cube::Faces::Bottom), Some((48, 0)));
+    }
+
+    #[test]
+    fn missing_face_texture_is_an_error() {
+        let definition: BlockDefinition = serde_json::from_str(r#"{ "name": "broken", "textures": { "top": [0, 0] } }"#).unwrap();
+
+        assert!(matches!(BlockBuilder::from_definition("blocks/broken.json", &definition), Err(BlockError::MissingTexture { .. })));
+    }
+
+    #[test]
+    fn tinted_faces_are_read_by_name() {
+        let definition: BlockDefinition = serde_json::from_str(r#"{ "name": "bush", "tint": ["top", "side"], "textures": { "all": [0, 0] } }"#).unwrap();
+        let mut blocks = HashMap::new();
+
+        BlockBuilder::from_definition("blocks/bush.json", &definition).unwrap().build(Some(&mut blocks));
+
+        let tinted = &blocks["bush"].tinted;
+
+        assert_eq!(tinted.len(), 5);
+        assert!(!tinted.contains(&cube::Faces::Bottom));
+
+        let broken: BlockDefinition = serde_json::from_str(r#"{ "name": "broken", "tint": ["up"], "textures": { "all": [0, 0] } }"#).unwrap();
+
+        assert!(matches!(BlockBuilder::from_definition("blocks/broken.json", &broken), Err(BlockError::UnknownFace { .. })));
+    }
+}
@@ -1,8 +1,23 @@
+use serde::{Deserialize, Serialize};
+
 use crate::nalgebra;
+use crate::settings;
 use crate::transform;
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Projection {
+    Perspective,
+    Orthographic
+}
+
 pub struct Camera {
     aspect_ratio: f32,
+    fov: f32,
+    near: f32,
+    far: f32,
+    projection: Projection,
+    orthographic_size: f32,
+    reverse_z: bool,
     pub transform: transform::Transform
 }
 
@@ -10,19 +25,131 @@ impl Camera {
     pub fn new() -> Camera {
         Camera {
             aspect_ratio: 1.0,
+            fov: 1.0,
+            near: 0.1,
+            far: 1024.0,
+            projection: Projection::Perspective,
+            orthographic_size: 64.0,
+            reverse_z: false,
             transform: transform::Transform::new()
         }
     }
 
+    pub fn apply_settings(&mut self, settings: &settings::CameraSettings) {
+        self.set_fov(settings.fov);
+        self.set_clip_planes(settings.near, settings.far);
+        self.set_projection(settings.projection);
+        self.set_orthographic_size(settings.orthographic_size);
+    }
+
     pub fn get_perspective(&self) -> [[f32; 4]; 4] {
-        let projection = nalgebra::Perspective3::new(self.aspect_ratio, 1.0, 0.1, 1024.0);
-        let projection_matrix = projection.to_homogeneous();
+        let mut projection_matrix = match self.projection {
+            Projection::Perspective => {
+                nalgebra::Perspective3::new(self.aspect_ratio, self.fov, self.near, self.far).to_homogeneous()
+            },
+            Projection::Orthographic => {
+                let half_height = self.orthographic_size / 2.0;
+                let half_width = half_height * self.aspect_ratio;
+
+                nalgebra::Orthographic3::new(-half_width, half_width, -half_height, half_height, self.near, self.far).to_homogeneous()
+            }
+        };
+
+        // Put the near plane at depth 1.0 and the far plane at 0.0, in a [0, 1] clip range. Float
+        // depth has most of its precision near 0, which then evens out the perspective divide
+        if self.reverse_z {
+            let (near, far) = (self.near, self.far);
+
+            let (scale, offset) = match self.projection {
+                Projection::Perspective => (near / (far - near), near * far / (far - near)),
+                Projection::Orthographic => (1.0 / (far - near), far / (far - near))
+            };
+
+            projection_matrix[(2, 2)] = scale;
+            projection_matrix[(2, 3)] = offset;
+        }
+
         let projection_ref: &[[f32; 4]; 4] = projection_matrix.as_ref();
 
         return *projection_ref;
     }
 
     pub fn update_aspect_ratio(&mut self, width: u32, height: u32) {
+        if (width == 0 || height == 0) {
+            return;
+        }
+
         self.aspect_ratio = width as f32 / height as f32;
     }
-}
\ No newline at end of file
+
+    // Vertical field of view, in degrees
+    pub fn set_fov(&mut self, degrees: f32) {
+        self.fov = degrees.clamp(1.0, 179.0).to_radians();
+    }
+
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near.max(0.001);
+        self.far = far.max(self.near + 0.001);
+    }
+
+    pub fn get_projection(&self) -> Projection {
+        return self.projection;
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    // Height of the orthographic view volume, in world units
+    pub fn set_orthographic_size(&mut self, size: f32) {
+        self.orthographic_size = size.max(0.001);
+    }
+
+    // Only turn this on with the clip range set to [0, 1], which the camera can't do itself
+    pub fn set_reverse_z(&mut self, reverse_z: bool) {
+        self.reverse_z = reverse_z;
+    }
+
+    // Depth test and clear value to pair with the current depth range
+    pub fn depth_test(&self) -> glium::draw_parameters::DepthTest {
+        if self.reverse_z {
+            glium::draw_parameters::DepthTest::IfMore
+        } else {
+            glium::draw_parameters::DepthTest::IfLess
+        }
+    }
+
+    pub fn depth_clear_value(&self) -> f32 {
+        if self.reverse_z { 0.0 } else { 1.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(camera: &Camera, distance: f32) -> f32 {
+        let matrix = nalgebra::Matrix4::from(camera.get_perspective());
+        let clip = matrix * nalgebra::Vector4::new(0.0, 0.0, -distance, 1.0);
+
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn reverse_z_puts_the_near_plane_at_one() {
+        for projection in [Projection::Perspective, Projection::Orthographic] {
+            let mut camera = Camera::new();
+            camera.set_projection(projection);
+            camera.set_clip_planes(0.5, 800.0);
+
+            assert!((depth(&camera, 0.5) + 1.0).abs() < 1e-4);
+            assert!((depth(&camera, 800.0) - 1.0).abs() < 1e-4);
+
+            camera.set_reverse_z(true);
+
+            assert!((depth(&camera, 0.5) - 1.0).abs() < 1e-4);
+            assert!(depth(&camera, 800.0).abs() < 1e-4);
+            assert!(depth(&camera, 10.0) > depth(&camera, 20.0));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+pub const CHUNK_SIZE: i32 = 16;
+pub const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+pub const AIR: u16 = 0;
+
+pub const MAX_LIGHT: u8 = 15;
+
+pub type ChunkPosition = (i32, i32, i32);
+
+#[derive(Clone)]
+pub struct Chunk {
+    blocks: Vec<u16>,
+    // Sky light in the high nibble and block light in the low one. Not saved, it's recomputed on load
+    light: Vec<u8>,
+    pub needs_mesh: bool,
+    // Changed since it was generated or last saved
+    pub needs_save: bool
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk {
+            blocks: vec![AIR; CHUNK_VOLUME],
+            light: vec![0; CHUNK_VOLUME],
+            needs_mesh: true,
+            needs_save: false
+        }
+    }
+
+    // Rebuild a chunk from its stored block ids, in the same order as `get_blocks`
+    pub fn from_blocks(blocks: Vec<u16>) -> Option<Chunk> {
+        if blocks.len() != CHUNK_VOLUME {
+            return None;
+        }
+
+        Some(Chunk {
+            blocks: blocks,
+            light: vec![0; CHUNK_VOLUME],
+            needs_mesh: true,
+            needs_save: false
+        })
+    }
+
+    pub fn get_blocks(&self) -> &[u16] {
+        return &self.blocks;
+    }
+
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        (x + z * CHUNK_SIZE + y * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+
+    // Coordinates are local to the chunk, 0..CHUNK_SIZE on every axis
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> u16 {
+        return self.blocks[Chunk::index(x, y, z)];
+    }
+
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: u16) {
+        self.blocks[Chunk::index(x, y, z)] = block;
+        self.needs_mesh = true;
+    }
+
+    pub fn get_sky_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        return self.light[Chunk::index(x, y, z)] >> 4;
+    }
+
+    pub fn set_sky_light(&mut self, x: i32, y: i32, z: i32, level: u8) {
+        let index = Chunk::index(x, y, z);
+        self.light[index] = (self.light[index] & 0x0f) | (level << 4);
+    }
+
+    pub fn get_block_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        return self.light[Chunk::index(x, y, z)] & 0x0f;
+    }
+
+    pub fn set_block_light(&mut self, x: i32, y: i32, z: i32, level: u8) {
+        let index = Chunk::index(x, y, z);
+        self.light[index] = (self.light[index] & 0xf0) | level;
+    }
+
+    pub fn clear_light(&mut self) {
+        self.light.fill(0);
+    }
+}
+
+// Split a world block coordinate into its chunk position and the local offset inside it
+pub fn to_chunk_coordinates(x: i32, y: i32, z: i32) -> (ChunkPosition, (i32, i32, i32)) {
+    (
+        (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE), z.div_euclid(CHUNK_SIZE)),
+        (x.rem_euclid(CHUNK_SIZE), y.rem_euclid(CHUNK_SIZE), z.rem_euclid(CHUNK_SIZE))
+    )
+}
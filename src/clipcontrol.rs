@@ -0,0 +1,92 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_uint};
+
+use glium::backend::Facade;
+
+// glium doesn't wrap glClipControl, so it is loaded straight from the driver
+const GL_LOWER_LEFT: c_uint = 0x8CA1;
+const GL_NEGATIVE_ONE_TO_ONE: c_uint = 0x935E;
+const GL_ZERO_TO_ONE: c_uint = 0x935F;
+const GL_EXTENSIONS: c_uint = 0x1F03;
+const GL_NUM_EXTENSIONS: c_uint = 0x821D;
+
+type ClipControlFn = extern "system" fn(origin: c_uint, depth: c_uint);
+type GetIntegervFn = extern "system" fn(name: c_uint, data: *mut c_int);
+type GetStringiFn = extern "system" fn(name: c_uint, index: c_uint) -> *const c_char;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DepthRange {
+    // OpenGL's default, clip space depth from -1 to 1
+    NegativeOneToOne,
+    // Depth lands in the buffer as it is, which is what reverse-Z needs to keep its float precision
+    ZeroToOne
+}
+
+// Switches the clip space depth range, on drivers with OpenGL 4.5 or ARB_clip_control
+pub struct ClipControl {
+    clip_control: ClipControlFn
+}
+
+impl ClipControl {
+    pub fn load(display: &glium::Display) -> Option<ClipControl> {
+        let context = display.get_context();
+        let version = context.get_opengl_version();
+
+        let core = version.0 == glium::Api::Gl && (version.1, version.2) >= (4, 5);
+
+        // Drivers hand out addresses for functions they don't implement, so support is checked first
+        unsafe {
+            context.exec_in_context(|| {
+                let gl_window = display.gl_window();
+
+                if !core && !has_extension(&gl_window, "GL_ARB_clip_control") {
+                    return None;
+                }
+
+                let address = gl_window.get_proc_address("glClipControl");
+
+                if address.is_null() {
+                    return None;
+                }
+
+                Some(ClipControl {
+                    clip_control: std::mem::transmute::<*const std::ffi::c_void, ClipControlFn>(address)
+                })
+            })
+        }
+    }
+
+    // Applies to everything drawn until it is changed back. glium doesn't know about this state,
+    // so the default has to be restored before anything that expects it is drawn
+    pub fn set_depth_range(&self, display: &glium::Display, range: DepthRange) {
+        let depth = match range {
+            DepthRange::NegativeOneToOne => GL_NEGATIVE_ONE_TO_ONE,
+            DepthRange::ZeroToOne => GL_ZERO_TO_ONE
+        };
+
+        unsafe {
+            display.get_context().exec_in_context(|| (self.clip_control)(GL_LOWER_LEFT, depth));
+        }
+    }
+}
+
+unsafe fn has_extension(gl_window: &glium::glutin::ContextWrapper<glium::glutin::PossiblyCurrent, glium::glutin::window::Window>, name: &str) -> bool {
+    let get_integerv = gl_window.get_proc_address("glGetIntegerv");
+    let get_stringi = gl_window.get_proc_address("glGetStringi");
+
+    if get_integerv.is_null() || get_stringi.is_null() {
+        return false;
+    }
+
+    let get_integerv = std::mem::transmute::<*const std::ffi::c_void, GetIntegervFn>(get_integerv);
+    let get_stringi = std::mem::transmute::<*const std::ffi::c_void, GetStringiFn>(get_stringi);
+
+    let mut count: c_int = 0;
+    get_integerv(GL_NUM_EXTENSIONS, &mut count);
+
+    return (0..count.max(0) as c_uint).any(|index| {
+        let extension = get_stringi(GL_EXTENSIONS, index);
+
+        !extension.is_null() && CStr::from_ptr(extension).to_bytes() == name.as_bytes()
+    });
+}
@@ -0,0 +1,189 @@
+use noise::{NoiseFn, Perlin};
+
+use crate::cube;
+use crate::meshbuilder;
+use crate::settings;
+
+// Width of one cloud cell in blocks, and the height of fancy clouds
+const CELL_SIZE: f32 = 12.0;
+const THICKNESS: f32 = 4.0;
+
+// Cells built in every direction around the camera
+const RADIUS: i32 = 24;
+
+// Blocks per second the layer drifts along +x
+const DRIFT_SPEED: f32 = 1.5;
+
+// Noise above this is cloud, higher values give clearer skies
+const COVERAGE: f64 = 0.1;
+
+pub struct Clouds {
+    perlin: Perlin,
+    mode: settings::CloudMode,
+    height: f32,
+    // How far the layer has moved along x
+    drift: f32,
+    // Cell the current mesh is centered on, the mesh is rebuilt when the camera leaves it
+    built_around: Option<(i32, i32)>
+}
+
+impl Clouds {
+    pub fn new(seed: u32, settings: &settings::GraphicsSettings) -> Clouds {
+        Clouds {
+            // Offset from the terrain seed so the clouds don't follow the hills
+            perlin: Perlin::new(seed.wrapping_add(1)),
+            mode: settings.clouds,
+            height: settings.cloud_height,
+            drift: 0.0,
+            built_around: None
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        self.drift += DRIFT_SPEED * delta;
+    }
+
+    // Meshes are built around the origin of the layer, this moves them into place
+    pub fn get_offset(&self) -> [f32; 3] {
+        return [self.drift, self.height, 0.0];
+    }
+
+    // Horizontal distances where clouds start to fade out and where they're gone, before the mesh ends
+    pub fn get_fade_range(&self) -> (f32, f32) {
+        let extent = RADIUS as f32 * CELL_SIZE;
+
+        return (extent * 0.6, extent * 0.95);
+    }
+
+    // A new mesh when the camera has moved over another cell of the drifting layer
+    pub fn rebuild(&mut self, camera: nalgebra::Point3<f32>) -> Option<meshbuilder::MeshBuilder> {
+        if self.mode == settings::CloudMode::Off {
+            return None;
+        }
+
+        let cell = (((camera.x - self.drift) / CELL_SIZE).floor() as i32, (camera.z / CELL_SIZE).floor() as i32);
+
+        if self.built_around == Some(cell) {
+            return None;
+        }
+
+        self.built_around = Some(cell);
+
+        return Some(self.build(cell));
+    }
+
+    fn is_cloud(&self, x: i32, z: i32) -> bool {
+        let (x, z) = (x as f64, z as f64);
+        let value = self.perlin.get([x * 0.11, z * 0.11]) + 0.5 * self.perlin.get([x * 0.31 + 100.0, z * 0.31]);
+
+        return value > COVERAGE;
+    }
+
+    fn build(&self, center: (i32, i32)) -> meshbuilder::MeshBuilder {
+        let mut mesh = meshbuilder::MeshBuilder::new();
+        let template = cube::Cube::new();
+
+        let thickness = match self.mode {
+            settings::CloudMode::Fancy => THICKNESS,
+            _ => 0.0
+        };
+
+        for x in (center.0 - RADIUS)..=(center.0 + RADIUS) {
+            for z in (center.1 - RADIUS)..=(center.1 + RADIUS) {
+                if !self.is_cloud(x, z) {
+                    continue;
+                }
+
+                for (face_type, face) in template.faces.iter() {
+                    let (ox, oy, oz) = face_type.offset();
+
+                    // Fast clouds are flat so only have a top and bottom. Fancy ones leave out the
+                    // sides between neighbouring cells
+                    let hidden = match self.mode {
+                        settings::CloudMode::Fancy => oy == 0 && self.is_cloud(x + ox, z + oz),
+                        _ => oy == 0
+                    };
+
+                    if hidden {
+                        continue;
+                    }
+
+                    let mut cell_face = face.clone();
+
+                    // The template cube spans -0.5 to 0.5, stretch it over the cell with its base at 0
+                    for vertex in cell_face.vertices.iter_mut() {
+                        let (vx, vy, vz) = vertex.position;
+
+                        vertex.position = (
+                            (x as f32 + vx + 0.5) * CELL_SIZE,
+                            (vy + 0.5) * thickness,
+                            (z as f32 + vz + 0.5) * CELL_SIZE
+                        );
+                    }
+
+                    mesh.add_face(cell_face);
+                }
+            }
+        }
+
+        return mesh;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_clouds(mode: settings::CloudMode) -> Clouds {
+        let graphics = settings::GraphicsSettings {
+            clouds: mode,
+            ..Default::default()
+        };
+
+        Clouds::new(7, &graphics)
+    }
+
+    #[test]
+    fn fast_clouds_are_flat() {
+        let mut clouds = test_clouds(settings::CloudMode::Fast);
+        let mesh = clouds.rebuild(nalgebra::Point3::origin()).unwrap();
+
+        assert!(!mesh.vertices.is_empty());
+        assert!(mesh.vertices.iter().all(|vertex| vertex.position.1 == 0.0 && vertex.normal.1 != 0.0));
+    }
+
+    #[test]
+    fn fancy_clouds_only_have_outer_sides() {
+        let mut clouds = test_clouds(settings::CloudMode::Fancy);
+        let mesh = clouds.rebuild(nalgebra::Point3::origin()).unwrap();
+
+        // Every side face sits on a boundary between cloud and clear sky. Faces are added six vertices at a time
+        for face in mesh.vertices.chunks(6).filter(|face| face[0].normal.1 == 0.0) {
+            let (nx, nz) = (face[0].normal.0 as i32, face[0].normal.2 as i32);
+            let center_x = face.iter().map(|vertex| vertex.position.0).sum::<f32>() / 6.0;
+            let center_z = face.iter().map(|vertex| vertex.position.2).sum::<f32>() / 6.0;
+
+            // Step back from the face into the cell it belongs to
+            let cell_x = ((center_x - nx as f32) / CELL_SIZE).floor() as i32;
+            let cell_z = ((center_z - nz as f32) / CELL_SIZE).floor() as i32;
+
+            assert!(clouds.is_cloud(cell_x, cell_z));
+            assert!(!clouds.is_cloud(cell_x + nx, cell_z + nz));
+        }
+    }
+
+    #[test]
+    fn mesh_is_rebuilt_only_when_the_camera_changes_cell() {
+        let mut clouds = test_clouds(settings::CloudMode::Fancy);
+
+        assert!(clouds.rebuild(nalgebra::Point3::new(1.0, 0.0, 1.0)).is_some());
+        assert!(clouds.rebuild(nalgebra::Point3::new(5.0, 0.0, 5.0)).is_none());
+
+        // Drifting a whole cell moves the camera over a different one
+        clouds.tick(CELL_SIZE / DRIFT_SPEED);
+
+        assert!(clouds.rebuild(nalgebra::Point3::new(5.0, 0.0, 5.0)).is_some());
+
+        assert!(test_clouds(settings::CloudMode::Off).rebuild(nalgebra::Point3::origin()).is_none());
+    }
+}
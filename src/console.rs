@@ -0,0 +1,130 @@
+use std::io::BufRead;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::daytime;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Help,
+    ShowTime,
+    // Fraction of the day
+    SetTime(f32),
+    FreezeTime(bool),
+    ShowBlock,
+    // Block placed from now on, by name
+    SelectBlock(String)
+}
+
+pub const HELP: &str = "Commands:
+  time                      show the time of day
+  time set <hour|name>      hour from 0 to 24, or sunrise, day, noon, sunset, night, midnight
+  time freeze | unfreeze    stop or restart the clock
+  block                     show the block that gets placed
+  block <name>              place this block from now on, e.g. lava or water";
+
+// Commands typed into the terminal the game was started from
+pub struct Console {
+    lines: mpsc::Receiver<String>
+}
+
+impl Console {
+    pub fn new() -> Console {
+        let (sender, receiver) = mpsc::channel();
+
+        // Reading stdin blocks, so it gets its own thread. It ends when stdin closes or the game does
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    },
+                    Err(_) => break
+                }
+            }
+        });
+
+        Console {
+            lines: receiver
+        }
+    }
+
+    // Commands entered since the last call. Anything that doesn't parse is reported straight away
+    pub fn poll(&self) -> Vec<Command> {
+        let mut commands = Vec::new();
+
+        for line in self.lines.try_iter() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match parse(&line) {
+                Ok(command) => commands.push(command),
+                Err(error) => eprintln!("{}", error)
+            }
+        }
+
+        return commands;
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    match words.as_slice() {
+        ["help"] => Ok(Command::Help),
+        ["time"] => Ok(Command::ShowTime),
+        ["time", "freeze"] => Ok(Command::FreezeTime(true)),
+        ["time", "unfreeze"] => Ok(Command::FreezeTime(false)),
+        ["time", "set", value] => parse_time(value).map(Command::SetTime),
+        ["block"] => Ok(Command::ShowBlock),
+        ["block", name] => Ok(Command::SelectBlock(name.to_string())),
+        _ => Err(format!("Unknown command '{}', try 'help'", line.trim()))
+    }
+}
+
+fn parse_time(value: &str) -> Result<f32, String> {
+    match value {
+        "sunrise" => Ok(daytime::SUNRISE),
+        "day" => Ok(daytime::MORNING),
+        "noon" => Ok(daytime::NOON),
+        "sunset" => Ok(daytime::SUNSET),
+        "midnight" | "night" => Ok(daytime::MIDNIGHT),
+        _ => {
+            match value.parse::<f32>() {
+                Ok(hours) if (0.0..=24.0).contains(&hours) => Ok(hours / 24.0),
+                _ => Err(format!("'{}' isn't an hour from 0 to 24 or a time name", value))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_commands_parse() {
+        assert_eq!(parse("time"), Ok(Command::ShowTime));
+        assert_eq!(parse("  time   freeze "), Ok(Command::FreezeTime(true)));
+        assert_eq!(parse("time unfreeze"), Ok(Command::FreezeTime(false)));
+        assert_eq!(parse("time set noon"), Ok(Command::SetTime(daytime::NOON)));
+        assert_eq!(parse("time set 18"), Ok(Command::SetTime(0.75)));
+    }
+
+    #[test]
+    fn block_commands_parse() {
+        assert_eq!(parse("block"), Ok(Command::ShowBlock));
+        assert_eq!(parse("block lava"), Ok(Command::SelectBlock("lava".to_string())));
+        assert!(parse("block lava water").is_err());
+    }
+
+    #[test]
+    fn bad_commands_are_rejected() {
+        assert!(parse("time set 25").is_err());
+        assert!(parse("time set teatime").is_err());
+        assert!(parse("weather clear").is_err());
+    }
+}
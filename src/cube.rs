@@ -9,7 +9,7 @@ pub struct Cube {
     pub faces: HashMap<Faces, Face>
 }
 
-#[derive(Eq, Hash, PartialEq, Clone)]
+#[derive(Eq, Hash, PartialEq, Clone, Debug)]
 pub enum Faces {
     Front,
     Back,
@@ -19,6 +19,19 @@ pub enum Faces {
     Bottom
 }
 
+impl Faces {
+    // Offset to the neighbouring block this face looks onto
+    pub fn offset(&self) -> (i32, i32, i32) {
+        match self {
+            Faces::Front => (0, 0, -1),
+            Faces::Back => (0, 0, 1),
+            Faces::Left => (-1, 0, 0),
+            Faces::Right => (1, 0, 0),
+            Faces::Top => (0, 1, 0),
+            Faces::Bottom => (0, -1, 0)
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Face {
@@ -32,6 +45,45 @@ impl Face {
             vertex.tex_coords = [vertex.tex_coords[0] * scale.0 + offset.0, vertex.tex_coords[1] * scale.0 + offset.1];
         }
     }
+
+    pub fn translate_local(&mut self, direction: nalgebra::Point3<f32>) {
+        for vertex in &mut self.vertices {
+            vertex.position = (vertex.position.0 + direction.x, vertex.position.1 + direction.y, vertex.position.2 + direction.z);
+        }
+    }
+
+    // Split the quad along the diagonal between its two brighter corners, so a single dark
+    // corner is confined to one triangle instead of streaking across the whole face
+    pub fn split_along_brighter_diagonal(&mut self) {
+        if self.indices.len() != 6 {
+            return;
+        }
+
+        let (first, second) = (self.indices[0..3].to_vec(), self.indices[3..6].to_vec());
+
+        // The corner of each triangle that isn't on the shared diagonal
+        let r = match first.iter().position(|index| !second.contains(index)) {
+            Some(r) => r,
+            None => return
+        };
+        let s = match second.iter().find(|index| !first.contains(index)) {
+            Some(s) => *s,
+            None => return
+        };
+
+        let (outer, x, y) = (first[r], first[(r + 1) % 3], first[(r + 2) % 3]);
+
+        let brightness = |index: u32| {
+            let vertex = &self.vertices[index as usize];
+
+            vertex.occlusion + vertex.light[0] + vertex.light[1]
+        };
+
+        if brightness(outer) + brightness(s) > brightness(x) + brightness(y) {
+            // Same winding, going round the quad as outer, x, s, y
+            self.indices = vec![outer, x, s, s, y, outer];
+        }
+    }
 }
 
 impl Cube {
@@ -43,32 +95,50 @@ impl Cube {
                         models::Vertex {
                             position: (-0.5, -0.5, -0.5),
                             normal: (0.0, 0.0, -1.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, -0.5),
                             normal: (0.0, 0.0, -1.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, -0.5),
                             normal: (0.0, 0.0, -1.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, -0.5),
                             normal: (0.0, 0.0, -1.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, 0.5, -0.5),
                             normal: (0.0, 0.0, -1.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, -0.5, -0.5),
                             normal: (0.0, 0.0, -1.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         }
                     ],
 
@@ -85,32 +155,50 @@ impl Cube {
                         models::Vertex {
                             position: (-0.5, -0.5, 0.5),
                             normal: (0.0, 0.0, 1.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, 0.5),
                             normal: (0.0, 0.0, 1.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, 0.5),
                             normal: (0.0, 0.0, 1.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, 0.5),
                             normal: (0.0, 0.0, 1.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, 0.5, 0.5),
                             normal: (0.0, 0.0, 1.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, -0.5, 0.5),
                             normal: (0.0, 0.0, 1.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         }
                     ],
 
@@ -127,32 +215,50 @@ impl Cube {
                         models::Vertex {
                             position: (0.5, 0.5, 0.5),
                             normal: (1.0, 0.0, 0.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, -0.5),
                             normal: (1.0, 0.0, 0.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, -0.5),
                             normal: (1.0, 0.0, 0.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, -0.5),
                             normal: (1.0, 0.0, 0.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, 0.5),
                             normal: (1.0, 0.0, 0.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, 0.5),
                             normal: (1.0, 0.0, 0.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         }
                     ],
 
@@ -169,32 +275,50 @@ impl Cube {
                         models::Vertex {
                             position: (-0.5, 0.5, 0.5),
                             normal: (-1.0, 0.0, 0.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, 0.5, -0.5),
                             normal: (-1.0, 0.0, 0.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, -0.5, -0.5),
                             normal: (-1.0, 0.0, 0.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, -0.5, -0.5),
                             normal: (-1.0, 0.0, 0.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, -0.5, 0.5),
                             normal: (-1.0, 0.0, 0.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, 0.5, 0.5),
                             normal: (-1.0, 0.0, 0.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         }
                     ],
 
@@ -211,32 +335,50 @@ impl Cube {
                         models::Vertex {
                             position: (-0.5, 0.5, 0.5),
                             normal: (0.0, 1.0, 0.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, 0.5),
                             normal: (0.0, 1.0, 0.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, -0.5),
                             normal: (0.0, 1.0, 0.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, 0.5, -0.5),
                             normal: (0.0, 1.0, 0.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, 0.5, -0.5),
                             normal: (0.0, 1.0, 0.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, 0.5, 0.5),
                             normal: (0.0, 1.0, 0.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         }
                     ],
 
@@ -253,32 +395,50 @@ impl Cube {
                         models::Vertex {
                             position: (-0.5, -0.5, 0.5),
                             normal: (0.0, -1.0, 0.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, 0.5),
                             normal: (0.0, -1.0, 0.0),
-                            tex_coords: [1.0, 0.0]
+                            tex_coords: [1.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, -0.5),
                             normal: (0.0, -1.0, 0.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (0.5, -0.5, -0.5),
                             normal: (0.0, -1.0, 0.0),
-                            tex_coords: [1.0, 1.0]
+                            tex_coords: [1.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, -0.5, -0.5),
                             normal: (0.0, -1.0, 0.0),
-                            tex_coords: [0.0, 1.0]
+                            tex_coords: [0.0, 1.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         },
                         models::Vertex {
                             position: (-0.5, -0.5, 0.5),
                             normal: (0.0, -1.0, 0.0),
-                            tex_coords: [0.0, 0.0]
+                            tex_coords: [0.0, 0.0],
+                            light: models::FULL_LIGHT,
+                            occlusion: 1.0,
+                            color: models::WHITE
                         }
                     ],
 
@@ -295,6 +455,7 @@ impl Cube {
         }
     }
 
+    #[allow(dead_code)]
     pub fn translate_local(&mut self, direction: nalgebra::Point3<f32>) {
         for face in self.faces.values_mut() {
             for vertex in face.vertices.iter_mut() {
@@ -303,6 +464,7 @@ impl Cube {
         }
     }
 
+    #[allow(dead_code)]
     pub fn scale_local(&mut self, scale: f32) {
         for face in self.faces.values_mut() {
             for vertex in face.vertices.iter_mut() {
@@ -311,3 +473,38 @@ impl Cube {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle_normals(face: &Face) -> Vec<nalgebra::Vector3<f32>> {
+        face.indices.chunks(3).map(|triangle| {
+            let corner = |index: u32| {
+                let position = face.vertices[index as usize].position;
+                nalgebra::Vector3::new(position.0, position.1, position.2)
+            };
+
+            (corner(triangle[1]) - corner(triangle[0])).cross(&(corner(triangle[2]) - corner(triangle[0]))).normalize()
+        }).collect()
+    }
+
+    #[test]
+    fn dark_corner_is_kept_off_the_diagonal() {
+        for (face_type, face) in Cube::new().faces.iter() {
+            let mut face = face.clone();
+            let normals = triangle_normals(&face);
+
+            // Darken one corner on the current diagonal
+            let dark = face.indices[0];
+            face.vertices[dark as usize].occlusion = 0.0;
+            face.split_along_brighter_diagonal();
+
+            let shared: Vec<&u32> = face.indices[0..3].iter().filter(|index| face.indices[3..6].contains(index)).collect();
+
+            assert_eq!(shared.len(), 2, "{:?}", face_type);
+            assert!(!shared.contains(&&dark), "{:?}", face_type);
+            assert_eq!(triangle_normals(&face), normals, "{:?} changed winding", face_type);
+        }
+    }
+}
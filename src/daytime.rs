@@ -0,0 +1,156 @@
+use std::f32::consts::PI;
+
+// Times of day as a fraction of the day, starting at midnight
+pub const MIDNIGHT: f32 = 0.0;
+pub const SUNRISE: f32 = 0.25;
+pub const MORNING: f32 = 0.3;
+pub const NOON: f32 = 0.5;
+pub const SUNSET: f32 = 0.75;
+
+// Sky light at night, as a fraction of full daylight
+const NIGHT_SKY_LIGHT: f32 = 0.15;
+
+// Tilt of the sun's path away from straight overhead, in radians
+const SUN_TILT: f32 = 0.35;
+
+const DAY_SKY: [f32; 3] = [0.35, 0.6, 0.95];
+const NIGHT_SKY: [f32; 3] = [0.01, 0.015, 0.04];
+const DAY_HORIZON: [f32; 3] = [0.75, 0.85, 0.95];
+const NIGHT_HORIZON: [f32; 3] = [0.03, 0.035, 0.07];
+const SUNSET_HORIZON: [f32; 3] = [0.95, 0.55, 0.3];
+
+pub struct WorldTime {
+    // Fraction of the day, 0 to 1
+    time: f32,
+    // Real seconds per day
+    day_length: f32,
+    frozen: bool
+}
+
+impl WorldTime {
+    pub fn new(time: f32, day_length: f32) -> WorldTime {
+        WorldTime {
+            time: time.rem_euclid(1.0),
+            day_length: day_length,
+            frozen: false
+        }
+    }
+
+    // Advance by a tick. A day length of 0 stops the clock
+    pub fn tick(&mut self, delta: f32) {
+        if (self.frozen || self.day_length <= 0.0) {
+            return;
+        }
+
+        self.time = (self.time + delta / self.day_length).rem_euclid(1.0);
+    }
+
+    pub fn get_time(&self) -> f32 {
+        return self.time;
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.rem_euclid(1.0);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        return self.frozen;
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    // Time of day on a 24 hour clock
+    pub fn get_hours(&self) -> f32 {
+        return self.time * 24.0;
+    }
+
+    // Unit vector pointing at the sun. It rises in +x, is highest at noon and sets in -x
+    pub fn sun_direction(&self) -> nalgebra::Vector3<f32> {
+        let angle = (self.time - SUNRISE) * 2.0 * PI;
+
+        nalgebra::Vector3::new(angle.cos(), angle.sin() * SUN_TILT.cos(), angle.sin() * SUN_TILT.sin()).normalize()
+    }
+
+    // Direction the world is lit from: the sun by day and the moon, opposite it, by night
+    pub fn light_direction(&self) -> nalgebra::Vector3<f32> {
+        let sun = self.sun_direction();
+
+        if sun.y > 0.0 { sun } else { -sun }
+    }
+
+    // How far into the day it is, 0 at night and 1 in full daylight, fading through dawn and dusk
+    fn daylight(&self) -> f32 {
+        let height = self.sun_direction().y;
+        let t = ((height + 0.1) / 0.35).clamp(0.0, 1.0);
+
+        return t * t * (3.0 - 2.0 * t);
+    }
+
+    // Multiplier for sky light in the world
+    pub fn sky_light(&self) -> f32 {
+        return NIGHT_SKY_LIGHT + (1.0 - NIGHT_SKY_LIGHT) * self.daylight();
+    }
+
+    // Colour of the sky overhead
+    pub fn sky_color(&self) -> [f32; 3] {
+        return mix(NIGHT_SKY, DAY_SKY, self.daylight());
+    }
+
+    // Colour at the horizon, which distant terrain fades into. It warms up while the sun is low
+    pub fn fog_color(&self) -> [f32; 3] {
+        let height = self.sun_direction().y;
+        let sunset = (-(height / 0.15).powi(2)).exp() * 0.6;
+
+        return mix(mix(NIGHT_HORIZON, DAY_HORIZON, self.daylight()), SUNSET_HORIZON, sunset);
+    }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_is_up_at_noon_and_down_at_midnight() {
+        let mut world_time = WorldTime::new(NOON, 600.0);
+
+        assert!(world_time.sun_direction().y > 0.9);
+        assert_eq!(world_time.sky_light(), 1.0);
+
+        world_time.set_time(MIDNIGHT);
+
+        assert!(world_time.sun_direction().y < -0.9);
+        assert_eq!(world_time.sky_light(), NIGHT_SKY_LIGHT);
+    }
+
+    #[test]
+    fn sun_is_on_the_horizon_at_sunrise_and_sunset() {
+        for time in [SUNRISE, SUNSET] {
+            let world_time = WorldTime::new(time, 600.0);
+
+            assert!(world_time.sun_direction().y.abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn time_wraps_and_can_be_frozen() {
+        let mut world_time = WorldTime::new(0.9, 10.0);
+
+        // A tenth of a day takes a second
+        for _ in 0..60 {
+            world_time.tick(1.0 / 30.0);
+        }
+
+        assert!((world_time.get_time() - 0.1).abs() < 1e-4);
+
+        world_time.set_frozen(true);
+        world_time.tick(5.0);
+
+        assert!((world_time.get_time() - 0.1).abs() < 1e-4);
+    }
+}
@@ -1,27 +1,60 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
 use glium::Surface;
 use glium::glutin;
 use glium::glutin::event;
-use glium::glutin::event::VirtualKeyCode;
-
-use noise::Seedable;
-use noise::{NoiseFn, Perlin};
 
 use glium::uniform;
 
+use crate::assets;
 use crate::textures;
 use crate::shaders;
 use crate::models;
-use crate::cube;
+use crate::biome;
 use crate::blockbuilder;
 use crate::camera;
+use crate::clipcontrol;
 use crate::meshbuilder;
+use crate::settings;
+use crate::save;
+use crate::chunk;
+use crate::world;
+use crate::player;
+use crate::physics;
+use crate::input;
+use crate::daytime;
+use crate::console;
+use crate::clouds;
+use crate::shadows;
+use crate::postprocess;
+#[cfg(feature = "gamepad")]
+use crate::gamepad;
+#[cfg(feature = "hot-reload")]
+use crate::hotreload;
+use crate::transform;
 use crate::nalgebra;
 
+const TICK_RATE: f64 = 60.0;
+const TICK_DELTA: f32 = (1.0 / TICK_RATE) as f32;
+
+// How far away blocks can be broken or placed, in blocks
+const REACH_DISTANCE: f32 = 6.0;
+
+// How many new chunk columns may be generated per tick
+const CHUNKS_PER_TICK: usize = 2;
+
+const MAX_RENDER_DISTANCE: i32 = 32;
+
 pub struct Game {
+    settings: settings::Settings,
+    // Off when the settings file couldn't be read, so it isn't overwritten with defaults
+    save_settings: bool,
+    assets: assets::Assets,
+    #[cfg(feature = "hot-reload")]
+    asset_watcher: Option<hotreload::AssetWatcher>,
     shaders: shaders::Shaders,
     models: models::Models,
     textures: textures::Textures,
@@ -29,12 +62,39 @@ pub struct Game {
     active_camera: camera::Camera,
     display: Option<glium::Display>,
     game_loop: fixedstep::FixedStep,
-    active_keys: HashMap<VirtualKeyCode, bool>,
-    active_mouse_buttons: HashMap<glutin::event::MouseButton, bool>,
+    frame_limiter: Option<spin_sleep::LoopHelper>,
+    input: input::Input,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gamepad::Gamepads>,
     window_focused: bool,
     cursor_locked: MouseState,
+    look_input: LookInput,
     delta_time: f32,
-    meshes: HashMap<String, meshbuilder::Mesh>
+    view: transform::Transform,
+    previous_view: transform::Transform,
+    look_delta: nalgebra::Vector2<f32>,
+    programs: HashMap<String, glium::Program>,
+    gpu_textures: HashMap<String, glium::texture::SrgbTexture2d>,
+    animations: textures::Animations,
+    meshes: HashMap<String, meshbuilder::Mesh>,
+    chunk_meshes: HashMap<chunk::ChunkPosition, meshbuilder::Mesh>,
+    // Transparent blocks like water, drawn after the rest of the terrain
+    translucent_chunk_meshes: HashMap<chunk::ChunkPosition, meshbuilder::Mesh>,
+    cloud_mesh: Option<meshbuilder::Mesh>,
+    // One depth texture per cascade, a single texel each while shadows are off
+    shadow_maps: Vec<glium::texture::DepthTexture2d>,
+    // The scene is drawn offscreen and finished by the post effects, made once the window exists
+    post_process: Option<postprocess::PostProcess>,
+    // Set when reverse-Z is on and the driver can switch the depth range for it
+    clip_control: Option<clipcontrol::ClipControl>,
+    world: world::World,
+    world_save: save::WorldSave,
+    ticks_until_autosave: u32,
+    world_time: daytime::WorldTime,
+    console: console::Console,
+    clouds: clouds::Clouds,
+    player: player::Player,
+    selected_block: String
 }
 
 #[derive(PartialEq)]
@@ -44,90 +104,501 @@ enum MouseState {
     Locked
 }
 
+// Where mouse look deltas come from while the cursor is locked
+#[derive(PartialEq)]
+enum LookInput {
+    // Raw device motion, with the cursor grabbed by the window
+    RawMotion,
+    // Cursor offset from the window centre, warped back every frame (for platforms that refuse the grab)
+    Recenter
+}
+
 impl Game {
     pub fn new() -> Game {
+        let (settings, save_settings) = match settings::Settings::load() {
+            Ok(settings) => (settings, true),
+            Err(error) => {
+                eprintln!("Using default settings, changes won't be saved: {}", error);
+                (settings::Settings::default(), false)
+            }
+        };
+        let mut active_camera = camera::Camera::new();
+
+        active_camera.apply_settings(&settings.camera);
+
+        let frame_limiter = match settings.graphics.frame_cap {
+            0 => None,
+            frame_cap => Some(spin_sleep::LoopHelper::builder().build_with_target_rate(frame_cap))
+        };
+
+        let packs = assets::find_packs(&settings.resource_packs);
+
+        for pack in packs.iter() {
+            println!("Using resource pack '{}': {}", pack.manifest.name, pack.manifest.description);
+        }
+
+        let world_save = save::WorldSave::new(Path::new(&settings.world.directory));
+        let world_time = daytime::WorldTime::new(daytime::MORNING, settings.world.day_length);
+        let clouds = clouds::Clouds::new(0, &settings.graphics);
+
+        let mut input = input::Input::new(settings.bindings.clone());
+        input.set_deadzone(settings.controls.gamepad_deadzone);
+
         Game {
+            settings: settings,
+            save_settings: save_settings,
+            assets: assets::Assets::new(packs),
+            #[cfg(feature = "hot-reload")]
+            asset_watcher: None,
             shaders: shaders::Shaders::new(),
             models: models::Models::new(),
             textures: textures::Textures::new(),
             blocks: HashMap::new(),
-            active_camera: camera::Camera::new(),
+            active_camera: active_camera,
             display: None,
-            game_loop: fixedstep::FixedStep::start(60.0),
-            active_keys: HashMap::new(),
-            active_mouse_buttons: HashMap::new(),
+            game_loop: fixedstep::FixedStep::start(TICK_RATE),
+            frame_limiter: frame_limiter,
+            input: input,
+            #[cfg(feature = "gamepad")]
+            gamepads: gamepad::Gamepads::new(),
             window_focused: true,
             cursor_locked: MouseState::Unlocked,
+            look_input: LookInput::RawMotion,
             delta_time: 1.0,
-            meshes: HashMap::new()
+            view: transform::Transform::new(),
+            previous_view: transform::Transform::new(),
+            look_delta: nalgebra::Vector2::zeros(),
+            programs: HashMap::new(),
+            gpu_textures: HashMap::new(),
+            animations: textures::Animations::new(),
+            meshes: HashMap::new(),
+            chunk_meshes: HashMap::new(),
+            translucent_chunk_meshes: HashMap::new(),
+            cloud_mesh: None,
+            shadow_maps: Vec::new(),
+            post_process: None,
+            clip_control: None,
+            world: world::World::new(0),
+            world_save: world_save,
+            ticks_until_autosave: 0,
+            world_time: world_time,
+            console: console::Console::new(),
+            clouds: clouds,
+            player: player::Player::new(nalgebra::Point3::origin()),
+            selected_block: "dirt".to_string()
         }
     }
 
     // Initialize base data
-    pub fn ready(&mut self) {
-        self.active_camera.transform.set_position(nalgebra::Point3::new(0.0, 2.0, 0.0));
-        self.active_camera.transform.set_rotation(nalgebra::Vector3::new(-1.5, 0.0, 0.0));
+    pub fn ready(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let display = self.display.as_ref().unwrap();
 
-        let window = self.display.as_ref().unwrap().gl_window();
-        window.window().focus_window();
+        display.gl_window().window().focus_window();
 
         self.cursor_locked = MouseState::NeedsLocked;
 
-        let mut mesh = meshbuilder::MeshBuilder::new();
+        // Compile programs and upload textures once, meshes refer to them by name
+        for shader_name in ["basic", "cloud", "post", "shadow", "sky"] {
+            self.programs.insert(shader_name.to_string(), self.shaders.get_shader_program(shader_name, display)?);
+        }
+
+        for texture_name in ["texture_atlas", "sky"] {
+            self.gpu_textures.insert(texture_name.to_string(), self.textures.get_texture(texture_name, display)?);
+        }
+
+        let size = display.gl_window().window().inner_size();
+        let post_process = postprocess::PostProcess::new(display, size.width, size.height, self.settings.graphics.msaa_samples)?;
+
+        println!("Drawing the scene with {}x MSAA", post_process.get_samples());
+
+        self.post_process = Some(post_process);
+
+        if self.settings.camera.reverse_z {
+            self.clip_control = clipcontrol::ClipControl::load(display);
+
+            if self.clip_control.is_none() {
+                eprintln!("Reverse-Z needs OpenGL 4.5 or ARB_clip_control, using the normal depth range");
+            }
+        }
+
+        self.active_camera.set_reverse_z(self.clip_control.is_some());
+
+        self.create_shadow_maps()?;
+
+        if self.world_save.exists() {
+            self.load_world()?;
+        } else {
+            self.create_world();
+        }
+
+        // Clouds follow the world's seed, so they can only be set up once it exists
+        self.clouds = clouds::Clouds::new(self.world.get_seed(), &self.settings.graphics);
 
-        mesh.set_texture(self.textures.get_texture("texture_atlas", self.display.as_ref().unwrap()).unwrap());
-        mesh.set_shader(self.shaders.get_shader_program("basic", &self.display.as_ref().unwrap()).unwrap());
+        self.previous_view = self.view;
+        self.active_camera.transform = self.view;
+        self.reset_autosave();
 
+        self.update_chunk_meshes();
+        self.update_clouds();
+
+        #[cfg(feature = "hot-reload")]
+        {
+            self.asset_watcher = hotreload::AssetWatcher::new(self.assets.get_roots());
+        }
+
+        return Ok(());
+    }
+
+    fn create_world(&mut self) {
         let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-        let perlin = Perlin::new().set_seed(time as u32);
 
-        for x in 1..100 {
-            for y in 1..100 {
-                let mut noise = perlin.get([(x as f64 / 4.0) * 0.1, (y as f64 / 4.0) * 0.1]);
-                noise = noise * 2.0 - 1.0;
-                noise = f64::floor(noise);
+        self.world = world::World::new(time as u32);
+        self.world.set_block_properties(&self.blocks);
+        self.world.set_color_jitter(self.settings.graphics.color_jitter);
+        self.apply_colormap();
+        self.world_time.set_time(daytime::MORNING);
+        self.world.generate_around(0, 0, self.render_distance(), usize::MAX);
 
-                
+        let spawn_height = self.world.surface_height(0, 0) + 1;
+        self.player = player::Player::new(nalgebra::Point3::new(0.5, spawn_height as f32, 0.5));
 
-                for cube in &self.blocks.get("grass").unwrap().cubes {
-                    let mut cube_clone = cube.clone();
+        self.view.set_position(self.player.eye_position());
+        self.view.set_euler_angles(nalgebra::Vector3::new(-0.3, 0.0, 0.0));
+    }
 
-                    cube_clone.translate_local(nalgebra::Point3::new(x as f32, noise as f32, y as f32));
-                    mesh.add_cube(cube_clone);
-                }
+    fn load_world(&mut self) -> Result<(), save::SaveError> {
+        let metadata = self.world_save.load_metadata()?;
+        let mut palette = metadata.palette;
+        let chunks = self.world_save.load_chunks(&mut palette)?;
 
-                // Fill in the dirt below the grass
-                for i in 1..25 {
-                    let z = noise as f32 - i as f32;
+        self.world = world::World::new(metadata.seed);
+        self.world.set_palette(palette);
+        self.world.set_block_properties(&self.blocks);
+        self.world.set_color_jitter(self.settings.graphics.color_jitter);
+        self.apply_colormap();
+        self.world_time.set_time(metadata.time_of_day);
 
-                    if (z <= -15.0) {
-                        break;
-                    }
+        for (position, saved_chunk) in chunks {
+            self.world.insert_chunk(position, saved_chunk);
+        }
 
-                    for cube in &self.blocks.get("dirt").unwrap().cubes {
-                        let mut cube_clone = cube.clone();
-    
-                        cube_clone.translate_local(nalgebra::Point3::new(x as f32, z, y as f32));
-                        mesh.add_cube(cube_clone);
+        let player_state = metadata.player;
+
+        self.player = player::Player::new(nalgebra::Point3::from(metadata.spawn));
+        self.player.set_position(nalgebra::Point3::from(player_state.position));
+        self.player.set_mode(player_state.mode);
+
+        let position = self.player.get_position();
+        self.world.generate_around(position.x.floor() as i32, position.z.floor() as i32, self.render_distance(), usize::MAX);
+
+        self.view.set_position(self.player.eye_position());
+        self.view.set_euler_angles(nalgebra::Vector3::from(player_state.rotation));
+
+        return Ok(());
+    }
+
+    // Write the changed chunks and the world metadata. Chunks stay marked as changed if anything fails
+    fn save_world(&mut self) {
+        let chunks = self.world.chunks_to_save();
+        let positions: Vec<chunk::ChunkPosition> = chunks.iter().map(|(position, _)| *position).collect();
+
+        let metadata = save::WorldMetadata {
+            format_version: save::FORMAT_VERSION,
+            seed: self.world.get_seed(),
+            spawn: self.player.get_spawn().into(),
+            player: save::PlayerState {
+                position: self.player.get_position().into(),
+                rotation: self.view.get_euler_angles().into(),
+                mode: self.player.get_mode()
+            },
+            time_of_day: self.world_time.get_time(),
+            palette: self.world.get_palette().to_vec()
+        };
+
+        let result = self.world_save.save_chunks(&chunks, self.world.get_palette())
+            .and_then(|_| self.world_save.save_metadata(&metadata));
+
+        match result {
+            Ok(_) => self.world.mark_saved(&positions),
+            Err(error) => eprintln!("Could not save the world: {}", error)
+        }
+    }
+
+    fn reset_autosave(&mut self) {
+        self.ticks_until_autosave = (self.settings.world.autosave_interval.max(0.0) as f64 * TICK_RATE) as u32;
+    }
+
+    // Pick up asset files edited since the last frame. Anything that fails to load keeps its last good version
+    #[cfg(feature = "hot-reload")]
+    fn reload_assets(&mut self) {
+        let changed = match self.asset_watcher.as_ref() {
+            Some(asset_watcher) => asset_watcher.take_changed(),
+            None => return
+        };
+
+        let mut blocks_changed = false;
+
+        for path in changed {
+            let (directory, file) = match path.split_once('/') {
+                Some(parts) => parts,
+                None => continue
+            };
+
+            match directory {
+                "shaders" => {
+                    if let Some(name) = file.strip_suffix("-vert.glsl").or_else(|| file.strip_suffix("-frag.glsl")) {
+                        self.reload_shader(name);
                     }
+                },
+                "textures" => {
+                    if file == textures::ANIMATIONS_FILE {
+                        self.reload_animations();
+                    } else if let Some(name) = file.strip_suffix(".png") {
+                        if name == "colormap" {
+                            self.reload_colormap();
+                        } else if self.animations.uses_image(name) {
+                            self.reload_animations();
+                        } else {
+                            self.reload_texture(name);
+
+                            // A fresh atlas has the still tiles back, the animations need copying over them again
+                            if name == "texture_atlas" {
+                                self.reload_animations();
+                            }
+                        }
+                    }
+                },
+                "models" => {
+                    if let Some(name) = file.strip_suffix(".obj") {
+                        match self.models.load_model(&self.assets, name) {
+                            Ok(_) => println!("Reloaded model '{}'", name),
+                            Err(error) => eprintln!("Keeping the previous model: {}", error)
+                        }
+                    }
+                },
+                "blocks" => {
+                    blocks_changed = true;
+                },
+                _ => {}
+            }
+        }
+
+        if blocks_changed {
+            match blockbuilder::load_blocks(&self.assets) {
+                Ok(blocks) => {
+                    self.blocks = blocks;
+                    self.world.set_block_properties(&self.blocks);
+                    self.world.mark_all_for_mesh();
+                    println!("Reloaded block definitions");
+                },
+                Err(error) => eprintln!("Keeping the previous block definitions: {}", error)
+            }
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn reload_shader(&mut self, name: &str) {
+        // Only programs that are in use get rebuilt
+        if !self.programs.contains_key(name) {
+            return;
+        }
+
+        let display = self.display.as_ref().unwrap();
+        let program = self.shaders.load_shader(&self.assets, name)
+            .and_then(|_| self.shaders.get_shader_program(name, display));
+
+        match program {
+            Ok(program) => {
+                self.programs.insert(name.to_string(), program);
+                println!("Reloaded shader '{}'", name);
+            },
+            Err(error) => eprintln!("Keeping the previous shader: {}", error)
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn reload_animations(&mut self) {
+        match textures::Animations::load(&self.assets) {
+            Ok(animations) => {
+                self.animations = animations;
+                println!("Reloaded texture animations");
+            },
+            Err(error) => eprintln!("Keeping the previous animations: {}", error)
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn reload_texture(&mut self, name: &str) {
+        if !self.gpu_textures.contains_key(name) {
+            return;
+        }
+
+        let display = self.display.as_ref().unwrap();
+        let texture = self.textures.load_image(&self.assets, name)
+            .and_then(|_| self.textures.get_texture(name, display));
+
+        match texture {
+            Ok(texture) => {
+                self.gpu_textures.insert(name.to_string(), texture);
+                println!("Reloaded texture '{}'", name);
+            },
+            Err(error) => eprintln!("Keeping the previous texture: {}", error)
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn reload_colormap(&mut self) {
+        match self.textures.load_image(&self.assets, "colormap") {
+            Ok(_) => {
+                self.apply_colormap();
+                self.world.mark_all_for_mesh();
+                println!("Reloaded texture 'colormap'");
+            },
+            Err(error) => eprintln!("Keeping the previous colormap: {}", error)
+        }
+    }
+
+    // Biome tints are read from the colormap image when chunks are meshed
+    fn apply_colormap(&mut self) {
+        match self.textures.get_rgba8("colormap") {
+            Ok(colormap) => self.world.set_colormap(biome::Colormap::new(colormap)),
+            Err(error) => eprintln!("Biome colours are off: {}", error)
+        }
+    }
+
+    // Copy the current frame of each animated tile into the atlas
+    fn update_animations(&mut self) {
+        let updates = self.animations.tick(TICK_DELTA);
+
+        if let Some(atlas) = self.gpu_textures.get("texture_atlas") {
+            for (position, pixels) in updates.iter() {
+                textures::write_tile(atlas, *position, pixels);
+            }
+        }
+    }
+
+    // Rebuild the meshes of chunks whose blocks (or neighbours) changed
+    pub fn update_chunk_meshes(&mut self) {
+        self.world.update_light();
+
+        let display = self.display.as_ref().unwrap();
+
+        for position in self.world.take_chunks_to_mesh() {
+            let chunk_mesh = self.world.mesh_chunk(position, &self.blocks);
+
+            for (mut mesh, meshes) in [(chunk_mesh.opaque, &mut self.chunk_meshes), (chunk_mesh.translucent, &mut self.translucent_chunk_meshes)] {
+                if mesh.vertices.is_empty() {
+                    meshes.remove(&position);
+                    continue;
                 }
+
+                mesh.set_texture("texture_atlas");
+                mesh.set_shader("basic");
+
+                meshes.insert(position, mesh.build(display));
+            }
+        }
+    }
+
+    // Rebuild the cloud mesh once the camera has moved over another part of the layer
+    pub fn update_clouds(&mut self) {
+        if let Some(mut mesh) = self.clouds.rebuild(self.view.get_position()) {
+            if mesh.vertices.is_empty() {
+                self.cloud_mesh = None;
+                return;
+            }
+
+            mesh.set_shader("cloud");
+
+            self.cloud_mesh = Some(mesh.build(self.display.as_ref().unwrap()));
+        }
+    }
+
+    fn create_shadow_maps(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let display = self.display.as_ref().unwrap();
+
+        // The basic shader always samples the maps, so they exist even with shadows off
+        let resolution = match self.settings.graphics.shadows {
+            true => self.settings.graphics.shadow_resolution.clamp(256, 8192),
+            false => 1
+        };
+
+        self.shadow_maps.clear();
+
+        for _ in 0..shadows::CASCADES {
+            let shadow_map = glium::texture::DepthTexture2d::empty_with_format(
+                display,
+                glium::texture::DepthFormat::F32,
+                glium::texture::MipmapsOption::NoMipmap,
+                resolution,
+                resolution
+            )?;
+
+            glium::framebuffer::SimpleFrameBuffer::depth_only(display, &shadow_map)?.clear_depth(1.0);
+
+            self.shadow_maps.push(shadow_map);
+        }
+
+        return Ok(());
+    }
+
+    // Offscreen targets follow the window size
+    fn resize_post_process(&mut self, width: u32, height: u32) {
+        let display = self.display.as_ref().unwrap();
+
+        if let Some(post_process) = self.post_process.as_mut() {
+            if let Err(error) = post_process.resize(display, width, height) {
+                eprintln!("Could not resize the offscreen scene: {}", error);
             }
         }
+    }
 
-        self.meshes.insert("flat".to_string(), mesh.build(self.display.as_ref().unwrap()));
+    // Shadow pass: chunk depth as seen from the light, into each cascade's map
+    fn draw_shadows(&self, cascades: &[shadows::Cascade]) {
+        let display = self.display.as_ref().unwrap();
+        let program = self.programs.get("shadow").expect("Shader not loaded");
+
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: self.active_camera.depth_test(),
+                write: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+
+        for (cascade, shadow_map) in cascades.iter().zip(self.shadow_maps.iter()) {
+            let mut target = glium::framebuffer::SimpleFrameBuffer::depth_only(display, shadow_map).unwrap();
+            target.clear_depth(1.0);
+
+            let uniforms = uniform! {
+                shadow_matrix: cascade.matrix
+            };
+
+            // Light passes through transparent blocks, so only the opaque terrain casts shadows
+            for mesh in self.chunk_meshes.values() {
+                target.draw(
+                    mesh.vertices.as_ref().expect("No vertices"),
+                    mesh.indices.as_ref().expect("No indices"),
+                    program,
+                    &uniforms,
+                    &params
+                ).unwrap();
+            }
+        }
     }
 
     // Update Skybox
     pub fn update_skybox(&mut self) {
         let mut sky = meshbuilder::MeshBuilder::new();
         
-        sky.set_texture(self.textures.get_texture("sky", self.display.as_ref().unwrap()).unwrap());
-        sky.set_shader(self.shaders.get_shader_program("sky", &self.display.as_ref().unwrap()).unwrap());
+        sky.set_texture("sky");
+        sky.set_shader("sky");
 
-        let mut skybox = self.models.get_model("skybox").unwrap();
+        let mut skybox = self.models.get_model("skybox").expect("skybox is loaded before the game starts");
 
         skybox.scale_local(1000.0);
-        skybox.translate_local(self.active_camera.transform.get_position().coords);
+        skybox.translate_local(self.view.get_position().coords);
 
         sky.add_model(skybox);
 
@@ -135,149 +606,345 @@ impl Game {
     }
 
     // Create information
-    pub fn create_window(&mut self, event_loop: &glutin::event_loop::EventLoop<()>, name: &str, width: u32, height: u32, _fullscreen: bool, vsync: bool) {
-        let icon_rgba = self.textures.icon_rgba8("icon");
-        let icon: Result<glutin::window::Icon, glutin::window::BadIcon> = glutin::window::Icon::from_rgba(icon_rgba, 400, 400);
-        
-        let window_builder = glutin::window::WindowBuilder::new()
-        .with_inner_size(glutin::dpi::LogicalSize::new(width, height))
+    pub fn create_window(&mut self, event_loop: &glutin::event_loop::EventLoop<()>, name: &str) -> Result<(), glium::backend::glutin::DisplayCreationError> {
+        let window_settings = &self.settings.window;
+        let graphics = &self.settings.graphics;
+
+        // A broken icon isn't worth refusing to start over
+        let icon = match self.textures.icon_rgba8(&self.assets, "icon") {
+            Ok((icon_rgba, icon_width, icon_height)) => match glutin::window::Icon::from_rgba(icon_rgba, icon_width, icon_height) {
+                Ok(icon) => Some(icon),
+                Err(error) => {
+                    eprintln!("Could not use the window icon: {}", error);
+                    None
+                }
+            },
+            Err(error) => {
+                eprintln!("Could not load the window icon: {}", error);
+                None
+            }
+        };
+
+        let mut window_builder = glutin::window::WindowBuilder::new()
+        .with_inner_size(glutin::dpi::LogicalSize::new(window_settings.width, window_settings.height))
+        .with_maximized(window_settings.maximized)
         .with_title(name)
-        .with_window_icon(Some(icon.unwrap()));
+        .with_window_icon(icon);
+
+        if let Some((x, y)) = window_settings.position {
+            window_builder = window_builder.with_position(glutin::dpi::PhysicalPosition::new(x, y));
+        }
 
+        // The scene has its own depth buffer and multisampling offscreen, the window only shows the result
         let context_builder = glutin::ContextBuilder::new()
-        .with_depth_buffer(24)
-        .with_vsync(vsync)
-        .with_multisampling(16);
+        .with_vsync(graphics.vsync);
 
-        let new_display = glium::Display::new(
-            window_builder, 
-            context_builder, 
-            event_loop
-        ).unwrap();
+        let new_display = glium::Display::new(window_builder, context_builder, event_loop)?;
 
-        self.active_camera.update_aspect_ratio(width, height);
+        println!("OpenGL context: vsync {}", if graphics.vsync { "on" } else { "off" });
+
+        // The projection works in physical pixels, which differ from the logical size on scaled displays
+        let size = new_display.gl_window().window().inner_size();
+        self.active_camera.update_aspect_ratio(size.width, size.height);
 
         self.display = Some(new_display);
+
+        self.apply_window_mode();
+
+        return Ok(());
     }
 
-    // Preload blocks
-    pub fn load_blocks(&mut self) {
-        let mut grass_cube = cube::Cube::new();
-
-        for (face_type, face) in grass_cube.faces.iter_mut() {
-            match face_type {
-                cube::Faces::Top => {
-                    face.set_face_texture_offset(
-                        (16.0 / 256.0, 16.0 / 256.0),
-                        (0.0 + (64.0 / 256.0), 1.0)
-                    );
-                },
-                cube::Faces::Bottom => {
-                    face.set_face_texture_offset(
-                        (16.0 / 256.0, 16.0 / 256.0),
-                        (0.0, 1.0)
-                    );
-                },
-                _ => {
-                    face.set_face_texture_offset(
-                        (16.0 / 256.0, 16.0 / 256.0),
-                        (0.0 + (32.0 / 256.0), 1.0)
-                    );
+    fn render_distance(&self) -> i32 {
+        return self.settings.graphics.render_distance.clamp(1, MAX_RENDER_DISTANCE);
+    }
+
+    // Fog start and end distances in blocks. The nearest chunk that isn't loaded is at least the
+    // render distance away, so fog ending before that hides where the world stops
+    fn fog_range(&self) -> (f32, f32) {
+        let distance = (self.render_distance() * chunk::CHUNK_SIZE) as f32;
+        let end = self.settings.graphics.fog_end.clamp(0.05, 1.0);
+        let start = self.settings.graphics.fog_start.clamp(0.0, end - 0.05);
+
+        return (start * distance, end * distance);
+    }
+
+    // Put the window into the mode from the settings, on whichever monitor it is on now
+    fn apply_window_mode(&mut self) {
+        let gl_window = self.display.as_ref().unwrap().gl_window();
+        let window = gl_window.window();
+
+        let fullscreen = match self.settings.window.mode {
+            settings::WindowMode::Windowed => None,
+            settings::WindowMode::Borderless => Some(glutin::window::Fullscreen::Borderless(window.current_monitor())),
+            settings::WindowMode::Exclusive => {
+                // Biggest resolution first, then the fastest refresh rate and deepest colour at that size
+                let video_mode = window.current_monitor().and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|mode| {
+                        (mode.size().width * mode.size().height, mode.refresh_rate_millihertz(), mode.bit_depth())
+                    })
+                });
+
+                match video_mode {
+                    Some(video_mode) => Some(glutin::window::Fullscreen::Exclusive(video_mode)),
+                    None => {
+                        eprintln!("No video modes available for exclusive fullscreen, using borderless");
+                        Some(glutin::window::Fullscreen::Borderless(window.current_monitor()))
+                    }
                 }
             }
+        };
+
+        window.set_fullscreen(fullscreen);
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let window_settings = &mut self.settings.window;
+
+        window_settings.mode = match window_settings.mode {
+            settings::WindowMode::Windowed => match window_settings.fullscreen_mode {
+                settings::WindowMode::Exclusive => settings::WindowMode::Exclusive,
+                _ => settings::WindowMode::Borderless
+            },
+            _ => settings::WindowMode::Windowed
+        };
+
+        self.apply_window_mode();
+    }
+
+    // Remember the size and position of the window, but only while it is a normal window
+    fn store_window_geometry(&mut self) {
+        if (self.settings.window.mode != settings::WindowMode::Windowed) {
+            return;
         }
 
-        blockbuilder::BlockBuilder::new()
-            .set_name("grass")
-            .set_transparent(false)
-            .add_cube(grass_cube)
-            .build(Some(&mut self.blocks));
+        let gl_window = self.display.as_ref().unwrap().gl_window();
+        let window = gl_window.window();
 
-        let mut dirt_cube = cube::Cube::new();
+        self.settings.window.maximized = window.is_maximized();
 
-        for (_face_type, face) in dirt_cube.faces.iter_mut() {
-            face.set_face_texture_offset(
-                (16.0 / 256.0, 16.0 / 256.0),
-                (0.0, 1.0)
-            );
+        // A maximized window keeps the size it should go back to
+        if self.settings.window.maximized {
+            return;
         }
 
-        blockbuilder::BlockBuilder::new()
-            .set_name("dirt")
-            .set_transparent(false)
-            .add_cube(dirt_cube)
-            .build(Some(&mut self.blocks));
+        let size: glutin::dpi::LogicalSize<u32> = window.inner_size().to_logical(window.scale_factor());
+
+        self.settings.window.width = size.width;
+        self.settings.window.height = size.height;
 
+        if let Ok(position) = window.outer_position() {
+            self.settings.window.position = Some((position.x, position.y));
+        }
+    }
+
+    fn save_settings(&mut self) {
+        if !self.save_settings {
+            return;
+        }
+
+        if let Err(error) = self.settings.save() {
+            eprintln!("Could not save settings: {}", error);
+        }
+    }
+
+    // Preload blocks
+    pub fn load_blocks(&mut self) -> Result<(), blockbuilder::BlockError> {
+        self.blocks = blockbuilder::load_blocks(&self.assets)?;
+
+        return Ok(());
     }
 
     // Preload Shaders
-    pub fn load_shaders(&mut self) {
-        self.shaders.load_shader("basic").unwrap();
-        self.shaders.load_shader("cloud").unwrap();
-        self.shaders.load_shader("sky").unwrap();
-        
+    pub fn load_shaders(&mut self) -> Result<(), shaders::ShaderError> {
+        self.shaders.load_shader(&self.assets, "basic")?;
+        self.shaders.load_shader(&self.assets, "cloud")?;
+        self.shaders.load_shader(&self.assets, "post")?;
+        self.shaders.load_shader(&self.assets, "shadow")?;
+        self.shaders.load_shader(&self.assets, "sky")?;
+
+        return Ok(());
     }
 
     // Preload Models
-    pub fn load_models(&mut self) {
-        self.models.load_model("skybox").unwrap();
+    pub fn load_models(&mut self) -> Result<(), models::ModelError> {
+        self.models.load_model(&self.assets, "skybox")?;
+
+        return Ok(());
     }
 
     // Preload Textures
-    pub fn load_textures(&mut self) {
-        self.textures.load_image("sky").unwrap();
-        self.textures.load_image("texture_atlas").unwrap();
+    pub fn load_textures(&mut self) -> Result<(), textures::TextureError> {
+        self.textures.load_image(&self.assets, "sky")?;
+        self.textures.load_image(&self.assets, "texture_atlas")?;
+        self.textures.load_image(&self.assets, "colormap")?;
+
+        self.animations = textures::Animations::load(&self.assets)?;
+
+        return Ok(());
+    }
+
+    // Turn the mouse movement gathered since the last tick, and the look stick, into camera rotation
+    fn apply_look(&mut self) {
+        let controls = &self.settings.controls;
+
+        let mut look_delta = self.look_delta * controls.mouse_sensitivity.to_radians();
+        self.look_delta = nalgebra::Vector2::zeros();
+
+        let stick = nalgebra::Vector2::new(
+            self.input.axis(input::Action::LookLeft, input::Action::LookRight),
+            self.input.axis(input::Action::LookUp, input::Action::LookDown)
+        );
+        look_delta += stick * controls.gamepad_look_speed.to_radians() * TICK_DELTA;
+
+        if controls.invert_y {
+            look_delta.y = -look_delta.y;
+        }
+
+        // Yaw around the world up axis, pitch around the camera's own right axis
+        self.view.rotate_world(
+            nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::y_axis(), -look_delta.x)
+        );
+        self.view.rotate_local(
+            nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::x_axis(), -look_delta.y)
+        );
+
+        // Clamp X rotation
+        let mut angles = self.view.get_euler_angles();
+        angles.x = angles.x.clamp(-1.5, 1.5);
+        angles.z = 0.0;
+        self.view.set_euler_angles(angles);
     }
 
     // Game tick
     pub fn game_tick(&mut self) -> bool {
-        let mut camera_speed: f32 = 0.4;
+        self.input.update();
+
+        if self.input.just_pressed(input::Action::Quit) {
+            self.save_world();
+            return true;
+        }
+
+        self.handle_actions();
+        self.run_commands();
+
+        self.world_time.tick(TICK_DELTA);
+        self.update_animations();
+        self.clouds.tick(TICK_DELTA);
+
+        let player_input = player::PlayerInput {
+            forward: self.input.axis(input::Action::MoveBackward, input::Action::MoveForward),
+            strafe: self.input.axis(input::Action::MoveLeft, input::Action::MoveRight),
+            vertical: self.input.axis(input::Action::MoveDown, input::Action::MoveUp),
+            jump: self.input.is_held(input::Action::Jump),
+            sneak: self.input.is_held(input::Action::Sneak)
+        };
 
-        match self.active_keys.get(&VirtualKeyCode::LShift) {
-            Some(true) => {
-                camera_speed /= 4.0;
+        // Keep the last tick's view around so rendering can blend between the two
+        self.previous_view = self.view;
+
+        self.apply_look();
+
+        self.player.tick(&player_input, &self.view, &self.world, TICK_DELTA);
+        self.view.set_position(self.player.eye_position());
+
+        let position = self.player.get_position();
+        let render_distance = self.render_distance();
+        self.world.generate_around(position.x.floor() as i32, position.z.floor() as i32, render_distance, CHUNKS_PER_TICK);
+
+        self.update_chunk_meshes();
+        self.update_skybox();
+        self.update_clouds();
+
+        // An interval of 0 leaves the countdown at 0, so only exiting saves
+        if self.ticks_until_autosave > 0 {
+            self.ticks_until_autosave -= 1;
+
+            if self.ticks_until_autosave == 0 {
+                self.save_world();
+                self.reset_autosave();
             }
-            _ => {}
         }
 
-        for (key, value) in self.active_keys.iter_mut() {
-            if *value {
-                match key {
-                    VirtualKeyCode::W => {
-                        self.active_camera.transform.translate_local(nalgebra::Vector3::new(0.0, 0.0, -camera_speed));
-                    }
-                    VirtualKeyCode::S => {
-                        self.active_camera.transform.translate_local(nalgebra::Vector3::new(0.0, 0.0, camera_speed));
-                    }
-                    VirtualKeyCode::A => {
-                        self.active_camera.transform.translate_local(nalgebra::Vector3::new(-camera_speed, 0.0, 0.0));
-                    }
-                    VirtualKeyCode::D => {
-                        self.active_camera.transform.translate_local(nalgebra::Vector3::new(camera_speed, 0.0, 0.0));
-                    }
-                    VirtualKeyCode::Q => {
-                        self.active_camera.transform.translate_local(nalgebra::Vector3::new(0.0, -camera_speed, 0.0));
-                    }
-                    VirtualKeyCode::E => {
-                        self.active_camera.transform.translate_local(nalgebra::Vector3::new(0.0, camera_speed, 0.0));
-                    }
-                    VirtualKeyCode::Escape => {
-                        return true;
+        return false;
+    }
+
+    fn run_commands(&mut self) {
+        for command in self.console.poll() {
+            match command {
+                console::Command::Help => println!("{}", console::HELP),
+                console::Command::ShowTime => {
+                    let hours = self.world_time.get_hours();
+                    let frozen = if self.world_time.is_frozen() { " (frozen)" } else { "" };
+
+                    println!("It's {:02}:{:02}{}", hours as u32, (hours.fract() * 60.0) as u32, frozen);
+                },
+                console::Command::SetTime(time) => {
+                    self.world_time.set_time(time);
+                    println!("Set the time to {:02}:{:02}", (time * 24.0) as u32, ((time * 24.0).fract() * 60.0) as u32);
+                },
+                console::Command::FreezeTime(frozen) => {
+                    self.world_time.set_frozen(frozen);
+                    println!("{}", if frozen { "Time is frozen" } else { "Time is running" });
+                },
+                console::Command::ShowBlock => println!("Placing {}", self.selected_block),
+                console::Command::SelectBlock(name) => {
+                    if self.blocks.contains_key(&name) {
+                        println!("Placing {}", name);
+                        self.selected_block = name;
+                    } else {
+                        let mut known: Vec<&str> = self.blocks.keys().map(|name| name.as_str()).collect();
+                        known.sort();
+
+                        eprintln!("There's no block called '{}', try one of: {}", name, known.join(", "));
                     }
-                    _ => {}
                 }
             }
         }
-
-        self.update_skybox();
-
-        return false;
     }
 
     // Drawing tick
     pub fn draw_tick(&mut self) {
-        let mut target = self.display.as_ref().unwrap().draw();
-        target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+        self.active_camera.transform = self.previous_view.interpolate(&self.view, self.delta_time.clamp(0.0, 1.0));
+
+        let sun_direction: [f32; 3] = self.world_time.sun_direction().into();
+        let sky_light = self.world_time.sky_light();
+        let sky_color = self.world_time.sky_color();
+        let fog_color = self.world_time.fog_color();
+        let (fog_start, fog_end) = self.fog_range();
+
+        // Shadows reach as far as chunks are loaded
+        let shadow_distance = (self.render_distance() * chunk::CHUNK_SIZE) as f32;
+        let shadow_resolution = self.shadow_maps[0].width();
+        let cascades = shadows::build_cascades(
+            self.active_camera.transform.get_position(),
+            self.world_time.light_direction(),
+            shadow_distance,
+            shadow_resolution
+        );
+
+        if self.settings.graphics.shadows {
+            self.draw_shadows(&cascades);
+        }
+
+        let shadow_behavior = glium::uniforms::SamplerBehavior {
+            minify_filter: glium::uniforms::MinifySamplerFilter::Linear,
+            magnify_filter: glium::uniforms::MagnifySamplerFilter::Linear,
+            wrap_function: (glium::uniforms::SamplerWrapFunction::Clamp, glium::uniforms::SamplerWrapFunction::Clamp, glium::uniforms::SamplerWrapFunction::Clamp),
+            depth_texture_comparison: Some(glium::uniforms::DepthTextureComparison::LessOrEqual),
+            ..Default::default()
+        };
+        let shadow_strength: f32 = if self.settings.graphics.shadows { 1.0 } else { 0.0 };
+
+        let display = self.display.as_ref().unwrap();
+        let post_process = self.post_process.as_ref().unwrap();
+
+        let mut target = post_process.scene_target(display);
+        // The shadow pass above and the post pass below both use the default range
+        if let Some(clip_control) = self.clip_control.as_ref() {
+            clip_control.set_depth_range(display, clipcontrol::DepthRange::ZeroToOne);
+        }
+
+        target.clear_color_and_depth((fog_color[0], fog_color[1], fog_color[2], 1.0), self.active_camera.depth_clear_value());
         
         let behavior = glium::uniforms::SamplerBehavior {
             minify_filter: glium::uniforms::MinifySamplerFilter::Nearest,
@@ -299,11 +966,36 @@ impl Game {
             .. Default::default()
         };
 
+        // Transparent blocks are blended over the terrain without hiding what's behind other
+        // translucent faces. Their back faces stay, so water's surface shows from below
+        let translucent_params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: self.active_camera.depth_test(),
+                write: false,
+                .. Default::default()
+            },
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+
         let cam_matrix = self.active_camera.transform.get_matrix();
         let cam_persp = self.active_camera.get_perspective();
         let cam_pos = *self.active_camera.transform.get_position().coords.as_ref();
 
-        for (_mesh_name, mesh) in &self.meshes {
+        // Translucent chunks go far to near, so nearer ones blend over the ones behind them
+        let camera_chunk = chunk::to_chunk_coordinates(cam_pos[0].floor() as i32, cam_pos[1].floor() as i32, cam_pos[2].floor() as i32).0;
+        let mut translucent: Vec<(&chunk::ChunkPosition, &meshbuilder::Mesh)> = self.translucent_chunk_meshes.iter().collect();
+        translucent.sort_by_key(|(position, _)| {
+            -((position.0 - camera_chunk.0).pow(2) + (position.1 - camera_chunk.1).pow(2) + (position.2 - camera_chunk.2).pow(2))
+        });
+
+        let opaque_pass = self.meshes.values().chain(self.chunk_meshes.values()).map(|mesh| (mesh, &params));
+        let translucent_pass = translucent.into_iter().map(|(_, mesh)| (mesh, &translucent_params));
+
+        for (mesh, mesh_params) in opaque_pass.chain(translucent_pass) {
+            let texture = self.gpu_textures.get(mesh.texture.as_ref().expect("No texture")).expect("Texture not loaded");
+            let program = self.programs.get(mesh.shader.as_ref().expect("No shader")).expect("Shader not loaded");
+
             let uniforms = uniform! {
                 model: [
                     [1.0, 0.0, 0.0, 0.0],
@@ -314,105 +1006,263 @@ impl Game {
                 view: cam_matrix,
                 perspective: cam_persp,
                 camera_position: cam_pos,
-                tex: glium::uniforms::Sampler(mesh.texture.as_ref().expect("No texture"), behavior)
+                sun_direction: sun_direction,
+                sky_light: sky_light,
+                sky_color: sky_color,
+                fog_color: fog_color,
+                fog_start: fog_start,
+                fog_end: fog_end,
+                tex: glium::uniforms::Sampler(texture, behavior),
+                shadow_map_0: glium::uniforms::Sampler(&self.shadow_maps[0], shadow_behavior),
+                shadow_map_1: glium::uniforms::Sampler(&self.shadow_maps[1], shadow_behavior),
+                shadow_map_2: glium::uniforms::Sampler(&self.shadow_maps[2], shadow_behavior),
+                shadow_matrix_0: cascades[0].matrix,
+                shadow_matrix_1: cascades[1].matrix,
+                shadow_matrix_2: cascades[2].matrix,
+                cascade_radius: [cascades[0].radius, cascades[1].radius, cascades[2].radius],
+                shadow_resolution: shadow_resolution as f32,
+                shadow_strength: shadow_strength
             };
 
             target.draw(
                 mesh.vertices.as_ref().expect("No vertices"), 
                 mesh.indices.as_ref().expect("No indices"), 
-                mesh.shader.as_ref().expect("No shader"),
+                program,
                 &uniforms, 
-                &params
+                mesh_params
             ).unwrap();
         }
 
-        target.finish().unwrap();
+        // Clouds go last so the terrain behind them shows through. They don't write depth, so
+        // overlapping cells don't cut holes in each other
+        if let Some(mesh) = self.cloud_mesh.as_ref() {
+            let program = self.programs.get("cloud").expect("Shader not loaded");
+            let offset = self.clouds.get_offset();
+            let (fade_start, fade_end) = self.clouds.get_fade_range();
+
+            let uniforms = uniform! {
+                model: [
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [offset[0], offset[1], offset[2], 1.0f32]
+                ],
+                view: cam_matrix,
+                perspective: cam_persp,
+                camera_position: cam_pos,
+                sky_light: sky_light,
+                fog_color: fog_color,
+                fade_start: fade_start,
+                fade_end: fade_end
+            };
+
+            let cloud_params = glium::DrawParameters {
+                depth: glium::Depth {
+                    test: self.active_camera.depth_test(),
+                    write: false,
+                    .. Default::default()
+                },
+                blend: glium::Blend::alpha_blending(),
+                backface_culling: glium::draw_parameters::BackfaceCullingMode::CullClockwise,
+                .. Default::default()
+            };
+
+            target.draw(
+                mesh.vertices.as_ref().expect("No vertices"),
+                mesh.indices.as_ref().expect("No indices"),
+                program,
+                &uniforms,
+                &cloud_params
+            ).unwrap();
+        }
+
+        if let Some(clip_control) = self.clip_control.as_ref() {
+            clip_control.set_depth_range(display, clipcontrol::DepthRange::NegativeOneToOne);
+        }
+
+        // Post pass: the effects take the scene to the window
+        let eye = self.active_camera.transform.get_position();
+        let eye_block = self.world.get_block(eye.x.floor() as i32, eye.y.floor() as i32, eye.z.floor() as i32);
+        let underwater = self.world.block_name(eye_block) == Some("water");
+
+        let mut frame = display.draw();
+
+        post_process.apply(
+            display,
+            &mut frame,
+            self.programs.get("post").expect("Shader not loaded"),
+            &self.settings.graphics.post_effects,
+            self.settings.graphics.exposure,
+            underwater
+        );
+
+        frame.finish().unwrap();
     }
 
-    pub fn keyboard_input(&mut self, event: &glium::glutin::event::WindowEvent<'_>) {
-        match *event {
-            glutin::event::WindowEvent::MouseInput { device_id: _, state, button, .. } => {
-                let pressed = state == glutin::event::ElementState::Pressed;
-
-                match button {
-                    glutin::event::MouseButton::Left => {
-                        self.active_mouse_buttons.insert(glutin::event::MouseButton::Left, pressed);
-                    },
-                    glutin::event::MouseButton::Right => {
-                        self.active_mouse_buttons.insert(glutin::event::MouseButton::Right, pressed);
-                    },
-                    _ => {}
+    // React to the edge-triggered actions, in a fixed order so a tick always plays out the same way
+    fn handle_actions(&mut self) {
+        if self.input.just_pressed(input::Action::ToggleCursor) {
+            match self.cursor_locked {
+                MouseState::Unlocked => {
+                    self.cursor_locked = MouseState::NeedsLocked;
+                },
+                _ => {
+                    self.cursor_locked = MouseState::Unlocked;
                 }
-            },
-            glutin::event::WindowEvent::KeyboardInput { input, .. } => {
-                let pressed = input.state == glutin::event::ElementState::Pressed;
-                match input.virtual_keycode {
-                    Some(key) => {
-                        if (pressed && key == VirtualKeyCode::LAlt) {
-                            match self.cursor_locked {
-                                MouseState::Unlocked => {
-                                    self.cursor_locked = MouseState::NeedsLocked;
-                                },
-                                _ => {
-                                    self.cursor_locked = MouseState::Unlocked;
-                                }
-                            }
-                        }
+            }
+        }
 
-                        self.active_keys.insert(key, pressed);
-                    },
-                    _ => {}
-                }
-            },
-            glutin::event::WindowEvent::CursorMoved { device_id: _, position, .. } => {
-                // Get the direction of the mouse movement
-                let window = self.display.as_ref().unwrap().gl_window();
+        // Debug view: flip between perspective and the orthographic map projection
+        if self.input.just_pressed(input::Action::ToggleProjection) {
+            let projection = match self.active_camera.get_projection() {
+                camera::Projection::Perspective => camera::Projection::Orthographic,
+                camera::Projection::Orthographic => camera::Projection::Perspective
+            };
 
-                let window_size = window.window().inner_size();
+            self.active_camera.set_projection(projection);
+        }
 
-                let (x, y) = (position.x as i32, position.y as i32);
-                
-                let (wx, wy) = (window_size.width as i32, window_size.height as i32);
+        if self.input.just_pressed(input::Action::ToggleFullscreen) {
+            self.toggle_fullscreen();
+        }
 
-                let (x_diff, y_diff) = (x - wx / 2, y - wy / 2);
+        if self.input.just_pressed(input::Action::ToggleSpectator) {
+            self.player.toggle_mode();
+        }
 
-                // Rotate the camera
-                
-                if (self.cursor_locked == MouseState::Locked && self.window_focused) {
-                    self.active_camera.transform.rotate_local(
-                        nalgebra::Vector3::new(-(y_diff as f32) * 0.01, -(x_diff as f32) * 0.01, 0.0)
-                    );
+        if self.input.just_pressed(input::Action::Break) {
+            self.break_block();
+        }
 
-                    // Clamp X rotation
-                    let mut old_rotation = self.active_camera.transform.get_rotation();
-                    old_rotation.x = old_rotation.x.min(1.5).max(-1.5);
-                    self.active_camera.transform.set_rotation(old_rotation);
+        if self.input.just_pressed(input::Action::Place) {
+            self.place_block();
+        }
+    }
 
-                    self.cursor_locked = MouseState::NeedsLocked;
+    // Controllers are read from their own queue rather than the window's events
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepads(&mut self) {
+        let events = match self.gamepads.as_mut() {
+            Some(gamepads) => gamepads.poll(),
+            None => return
+        };
+
+        // Other windows own the controller while we're in the background
+        if !self.window_focused {
+            return;
+        }
+
+        for event in events {
+            self.input.handle_gamepad_event(event);
+        }
+    }
+
+    fn target_block(&self) -> Option<physics::RayHit> {
+        if (self.cursor_locked == MouseState::Unlocked) {
+            return None;
+        }
+
+        physics::raycast(&self.world, self.view.get_position(), self.view.forward(), REACH_DISTANCE)
+    }
+
+    fn break_block(&mut self) {
+        if let Some(hit) = self.target_block() {
+            let (x, y, z) = hit.block;
+
+            self.world.set_block(x, y, z, chunk::AIR);
+        }
+    }
+
+    fn place_block(&mut self) {
+        let hit = match self.target_block() {
+            Some(hit) => hit,
+            None => return
+        };
+
+        if hit.normal == (0, 0, 0) {
+            return;
+        }
+
+        let (x, y, z) = (hit.block.0 + hit.normal.0, hit.block.1 + hit.normal.1, hit.block.2 + hit.normal.2);
+
+        // Don't wall the player in
+        if (self.player.get_mode() == player::MovementMode::Walking && physics::Aabb::from_block(x, y, z).intersects(&self.player.get_aabb())) {
+            return;
+        }
+
+        let block_id = self.world.block_id(&self.selected_block);
+        self.world.set_block(x, y, z, block_id);
+    }
+
+    pub fn keyboard_input(&mut self, event: &glium::glutin::event::WindowEvent<'_>) {
+        match *event {
+            glutin::event::WindowEvent::MouseInput { .. } | glutin::event::WindowEvent::KeyboardInput { .. } => {
+                self.input.handle_window_event(event);
+            },
+            glutin::event::WindowEvent::CursorMoved { device_id: _, position, .. } => {
+                if (self.look_input != LookInput::Recenter || self.cursor_locked != MouseState::Locked || !self.window_focused) {
+                    return;
                 }
+
+                // Fallback look: measure how far the cursor drifted from the centre, then warp it back
+                let window_size = self.display.as_ref().unwrap().gl_window().window().inner_size();
+
+                let x_diff = position.x - (window_size.width / 2) as f64;
+                let y_diff = position.y - (window_size.height / 2) as f64;
+
+                // Rotation is applied on the next game tick
+                self.look_delta += nalgebra::Vector2::new(x_diff as f32, y_diff as f32);
+
+                self.cursor_locked = MouseState::NeedsLocked;
             },
             _ => return
         }
     }
 
+    // Event kinds are matched out one level at a time, with room left for the ones not handled yet
+    #[allow(clippy::single_match, clippy::collapsible_match)]
     pub fn process_events(&mut self, event: event::Event<()>) -> bool {
         match event {
             glutin::event::Event::WindowEvent { event, .. } => match event {
                 glutin::event::WindowEvent::CloseRequested => {
+                    self.save_world();
                     return true;
                 },
                 glutin::event::WindowEvent::Resized(physical_size) => {
                     self.active_camera.update_aspect_ratio(physical_size.width, physical_size.height);
+                    self.resize_post_process(physical_size.width, physical_size.height);
+                    self.store_window_geometry();
+                },
+                glutin::event::WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    self.active_camera.update_aspect_ratio(new_inner_size.width, new_inner_size.height);
+                    self.resize_post_process(new_inner_size.width, new_inner_size.height);
+                },
+                glutin::event::WindowEvent::Moved(_) => {
+                    self.store_window_geometry();
                 },
                 glutin::event::WindowEvent::Focused(focused) => {
                     if !focused {
-                        self.active_keys.clear();
+                        self.input.clear();
+                    }
+
+                    // The grab is released when focus is lost, so take it again on return
+                    if (focused && self.cursor_locked == MouseState::Locked) {
+                        self.cursor_locked = MouseState::NeedsLocked;
                     }
 
                     self.window_focused = focused;
                 },
                 sub_event => self.keyboard_input(&sub_event),
             },
+            glutin::event::Event::DeviceEvent { event, .. } => match event {
+                glutin::event::DeviceEvent::MouseMotion { delta } => {
+                    // Rotation is applied on the next game tick
+                    if (self.look_input == LookInput::RawMotion && self.cursor_locked == MouseState::Locked && self.window_focused) {
+                        self.look_delta += nalgebra::Vector2::new(delta.0 as f32, delta.1 as f32);
+                    }
+                },
+                _ => (),
+            },
             glutin::event::Event::NewEvents(cause) => match cause {
                 glutin::event::StartCause::ResumeTimeReached { .. } => {
                 }
@@ -427,28 +1277,38 @@ impl Game {
         return false;
     }
 
-    pub fn center_cursor(&mut self) {
-        let window = self.display.as_ref().unwrap().gl_window();
-        let (width, height) = self.display.as_ref().unwrap().get_framebuffer_dimensions();
+    // Hide and grab the cursor for mouse look, dropping to the recentering fallback if the grab is refused
+    pub fn lock_cursor(&mut self) {
+        let gl_window = self.display.as_ref().unwrap().gl_window();
+        let window = gl_window.window();
 
-        let x = (width as f32 / 2.0) as i32;
-        let y = (height as f32 / 2.0) as i32;
-    
-        self.display.as_ref().unwrap().gl_window().window().set_cursor_visible(!(self.window_focused && (self.cursor_locked != MouseState::Unlocked)));
+        window.set_cursor_visible(!self.window_focused);
 
-        match window.window().set_cursor_position(glutin::dpi::LogicalPosition::new(x, y)) {
-            Ok(_) => {
-                match window.window().set_cursor_grab(glutin::window::CursorGrabMode::Confined) {
-                    _ => {}
+        if (self.look_input == LookInput::RawMotion) {
+            let grabbed = window.set_cursor_grab(glutin::window::CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(glutin::window::CursorGrabMode::Confined));
+
+            match grabbed {
+                Ok(_) => return,
+                Err(error) => {
+                    eprintln!("Could not grab the cursor ({}), falling back to recentering it", error);
+                    self.look_input = LookInput::Recenter;
                 }
             }
-            _ => {}
+        }
+
+        let window_size = window.inner_size();
+        let center = glutin::dpi::PhysicalPosition::new(window_size.width / 2, window_size.height / 2);
+
+        if let Err(error) = window.set_cursor_position(center) {
+            eprintln!("Could not move the cursor: {}", error);
         }
     }
 
+    #[allow(clippy::match_single_binding)]
     pub fn window_update(&mut self) {
         if (self.cursor_locked == MouseState::NeedsLocked) {
-            self.center_cursor();
+            self.lock_cursor();
             self.cursor_locked = MouseState::Locked;
         }
 
@@ -467,8 +1327,18 @@ impl Game {
         event_loop.run(move |event, _, control_flow| {
             match event {
                 glutin::event::Event::MainEventsCleared => {
+                    if let Some(frame_limiter) = self.frame_limiter.as_mut() {
+                        frame_limiter.loop_start();
+                    }
+
                     self.window_update();
 
+                    #[cfg(feature = "gamepad")]
+                    self.poll_gamepads();
+
+                    #[cfg(feature = "hot-reload")]
+                    self.reload_assets();
+
                     while self.game_loop.update() {
                         let game_exit = self.game_tick();
                         
@@ -477,8 +1347,17 @@ impl Game {
                         }
                     }
                     
+                    // Fraction of a tick that has passed since the last update, used to interpolate the camera
                     self.delta_time = self.game_loop.render_delta() as f32;
                     self.draw_tick();
+
+                    if let Some(frame_limiter) = self.frame_limiter.as_mut() {
+                        frame_limiter.loop_sleep();
+                    }
+                },
+                glutin::event::Event::LoopDestroyed => {
+                    self.save_settings();
+                    return;
                 },
                 _ => ()
             }
@@ -490,4 +1369,4 @@ impl Game {
             }
         });
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,99 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+use crate::input;
+
+// Reads every connected controller and translates its events for the input system
+pub struct Gamepads {
+    gilrs: Gilrs
+}
+
+impl Gamepads {
+    pub fn new() -> Option<Gamepads> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Gamepads { gilrs: gilrs }),
+            Err(error) => {
+                eprintln!("Gamepads are unavailable: {}", error);
+                None
+            }
+        }
+    }
+
+    // Drain the events that arrived since the last poll
+    pub fn poll(&mut self) -> Vec<input::GamepadEvent> {
+        let mut events = Vec::new();
+
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            if let Some(event) = translate_event(id.into(), event) {
+                events.push(event);
+            }
+        }
+
+        return events;
+    }
+}
+
+fn translate_event(id: input::GamepadId, event: EventType) -> Option<input::GamepadEvent> {
+    match event {
+        // Analog triggers report how far they are pulled as a button value
+        EventType::ButtonChanged(Button::LeftTrigger2, value, _) => Some(input::GamepadEvent::Axis(id, input::GamepadAxis::LeftTrigger, value)),
+        EventType::ButtonChanged(Button::RightTrigger2, value, _) => Some(input::GamepadEvent::Axis(id, input::GamepadAxis::RightTrigger, value)),
+        EventType::ButtonPressed(button, _) => translate_button(button).map(|button| input::GamepadEvent::Button(id, button, true)),
+        EventType::ButtonReleased(button, _) => translate_button(button).map(|button| input::GamepadEvent::Button(id, button, false)),
+        EventType::AxisChanged(axis, value, _) => translate_axis(axis).map(|axis| input::GamepadEvent::Axis(id, axis, value)),
+        EventType::Disconnected => Some(input::GamepadEvent::Disconnected(id)),
+        _ => None
+    }
+}
+
+fn translate_button(button: Button) -> Option<input::GamepadButton> {
+    match button {
+        Button::South => Some(input::GamepadButton::South),
+        Button::East => Some(input::GamepadButton::East),
+        Button::West => Some(input::GamepadButton::West),
+        Button::North => Some(input::GamepadButton::North),
+        Button::LeftTrigger => Some(input::GamepadButton::LeftBumper),
+        Button::RightTrigger => Some(input::GamepadButton::RightBumper),
+        Button::Select => Some(input::GamepadButton::Select),
+        Button::Start => Some(input::GamepadButton::Start),
+        Button::LeftThumb => Some(input::GamepadButton::LeftStick),
+        Button::RightThumb => Some(input::GamepadButton::RightStick),
+        Button::DPadUp => Some(input::GamepadButton::DPadUp),
+        Button::DPadDown => Some(input::GamepadButton::DPadDown),
+        Button::DPadLeft => Some(input::GamepadButton::DPadLeft),
+        Button::DPadRight => Some(input::GamepadButton::DPadRight),
+        _ => None
+    }
+}
+
+fn translate_axis(axis: Axis) -> Option<input::GamepadAxis> {
+    match axis {
+        Axis::LeftStickX => Some(input::GamepadAxis::LeftStickX),
+        Axis::LeftStickY => Some(input::GamepadAxis::LeftStickY),
+        Axis::RightStickX => Some(input::GamepadAxis::RightStickX),
+        Axis::RightStickY => Some(input::GamepadAxis::RightStickY),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Button and axis events carry a gilrs `Code`, which only a real device can produce, so those
+    // are covered through the functions they are translated with
+    #[test]
+    fn disconnect_keeps_the_gamepad_id() {
+        assert_eq!(translate_event(3, EventType::Disconnected), Some(input::GamepadEvent::Disconnected(3)));
+        assert_eq!(translate_event(3, EventType::Connected), None);
+    }
+
+    #[test]
+    fn unmapped_buttons_and_axes_are_ignored() {
+        assert_eq!(translate_button(Button::South), Some(input::GamepadButton::South));
+        assert_eq!(translate_button(Button::LeftTrigger), Some(input::GamepadButton::LeftBumper));
+        assert_eq!(translate_button(Button::Mode), None);
+
+        assert_eq!(translate_axis(Axis::RightStickY), Some(input::GamepadAxis::RightStickY));
+        assert_eq!(translate_axis(Axis::LeftZ), None);
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use notify::Watcher;
+
+use crate::assets;
+
+// Watches the asset directories on disk, in the source tree and in directory resource packs, and reports which files were written
+pub struct AssetWatcher {
+    _watcher: notify::RecommendedWatcher,
+    roots: Vec<PathBuf>,
+    events: mpsc::Receiver<notify::Result<notify::Event>>
+}
+
+impl AssetWatcher {
+    pub fn new(roots: Vec<PathBuf>) -> Option<AssetWatcher> {
+        let (sender, receiver) = mpsc::channel();
+
+        let mut watcher = match notify::recommended_watcher(sender) {
+            Ok(watcher) => watcher,
+            Err(error) => {
+                eprintln!("Hot reloading is unavailable: {}", error);
+                return None;
+            }
+        };
+
+        // Events come back with the same prefix that was watched, so compare against canonical paths
+        let roots: Vec<PathBuf> = roots.into_iter()
+            .map(|root| root.canonicalize().unwrap_or(root))
+            .collect();
+
+        for root in roots.iter() {
+            for directory in assets::ASSET_DIRECTORIES {
+                let path = root.join(directory);
+
+                // Packs only need the directories they override
+                if !path.is_dir() {
+                    continue;
+                }
+
+                if let Err(error) = watcher.watch(&path, notify::RecursiveMode::Recursive) {
+                    eprintln!("Not watching {}: {}", path.display(), error);
+                }
+            }
+        }
+
+        Some(AssetWatcher {
+            _watcher: watcher,
+            roots: roots,
+            events: receiver
+        })
+    }
+
+    // Asset paths written since the last call, like "shaders/basic-frag.glsl", each listed once
+    pub fn take_changed(&self) -> Vec<String> {
+        let mut changed: Vec<String> = Vec::new();
+
+        while let Ok(result) = self.events.try_recv() {
+            let event = match result {
+                Ok(event) => event,
+                Err(error) => {
+                    eprintln!("Asset watcher error: {}", error);
+                    continue;
+                }
+            };
+
+            // Editors that save through a temporary file show up as creates
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                continue;
+            }
+
+            for path in event.paths {
+                if let Some(relative) = asset_path(&self.roots, &path) {
+                    if !changed.contains(&relative) {
+                        changed.push(relative);
+                    }
+                }
+            }
+        }
+
+        return changed;
+    }
+}
+
+// Path of a file relative to the root it is in. Packs sit inside the source tree, so the most
+// specific root wins
+fn asset_path(roots: &[PathBuf], path: &Path) -> Option<String> {
+    let root = roots.iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())?;
+
+    let relative = path.strip_prefix(root).ok()?;
+
+    return Some(relative.to_string_lossy().replace('\\', "/"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_in_nested_packs_are_relative_to_the_pack() {
+        let roots = vec![PathBuf::from("/game"), PathBuf::from("/game/resourcepacks/dark")];
+
+        assert_eq!(asset_path(&roots, Path::new("/game/shaders/basic-frag.glsl")), Some("shaders/basic-frag.glsl".to_string()));
+        assert_eq!(asset_path(&roots, Path::new("/game/resourcepacks/dark/shaders/basic-frag.glsl")), Some("shaders/basic-frag.glsl".to_string()));
+        assert_eq!(asset_path(&roots, Path::new("/elsewhere/textures/icon.png")), None);
+    }
+}
@@ -0,0 +1,589 @@
+use std::collections::{HashMap, HashSet};
+
+use glium::glutin::event::{ElementState, MouseButton, VirtualKeyCode, WindowEvent};
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Sneak,
+    Break,
+    Place,
+    ToggleCursor,
+    ToggleSpectator,
+    ToggleProjection,
+    ToggleFullscreen,
+    Quit,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger
+}
+
+impl GamepadAxis {
+    // The other axis of the same stick, triggers have none
+    fn partner(&self) -> Option<GamepadAxis> {
+        match self {
+            GamepadAxis::LeftStickX => Some(GamepadAxis::LeftStickY),
+            GamepadAxis::LeftStickY => Some(GamepadAxis::LeftStickX),
+            GamepadAxis::RightStickX => Some(GamepadAxis::RightStickY),
+            GamepadAxis::RightStickY => Some(GamepadAxis::RightStickX),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+    // One half of an analog axis, sticks point up and right for positive values
+    GamepadAxis(GamepadAxis, AxisDirection)
+}
+
+// Tells connected controllers apart, so one going away doesn't release another's buttons
+pub type GamepadId = usize;
+
+// Controller input, independent of the library that reads the device
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+pub enum GamepadEvent {
+    Button(GamepadId, GamepadButton, bool),
+    Axis(GamepadId, GamepadAxis, f32),
+    Disconnected(GamepadId)
+}
+
+// What one controller is currently holding
+#[derive(Default)]
+struct GamepadState {
+    buttons: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>
+}
+
+// How far an analog binding has to move before it counts as held
+const PRESS_THRESHOLD: f32 = 0.5;
+
+// Every binding attached to each action. Actions missing from the config keep their defaults.
+// Saved as the same flat map of actions that is read back in
+#[derive(Clone, PartialEq, Debug, Serialize)]
+#[serde(transparent)]
+pub struct Bindings {
+    actions: HashMap<Action, Vec<Binding>>
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        let defaults = [
+            (Action::MoveForward, vec![Binding::Key(VirtualKeyCode::W), Binding::GamepadAxis(GamepadAxis::LeftStickY, AxisDirection::Positive)]),
+            (Action::MoveBackward, vec![Binding::Key(VirtualKeyCode::S), Binding::GamepadAxis(GamepadAxis::LeftStickY, AxisDirection::Negative)]),
+            (Action::MoveLeft, vec![Binding::Key(VirtualKeyCode::A), Binding::GamepadAxis(GamepadAxis::LeftStickX, AxisDirection::Negative)]),
+            (Action::MoveRight, vec![Binding::Key(VirtualKeyCode::D), Binding::GamepadAxis(GamepadAxis::LeftStickX, AxisDirection::Positive)]),
+            (Action::MoveUp, vec![Binding::Key(VirtualKeyCode::E), Binding::Gamepad(GamepadButton::South)]),
+            (Action::MoveDown, vec![Binding::Key(VirtualKeyCode::Q), Binding::Gamepad(GamepadButton::East)]),
+            (Action::Jump, vec![Binding::Key(VirtualKeyCode::Space), Binding::Gamepad(GamepadButton::South)]),
+            (Action::Sneak, vec![Binding::Key(VirtualKeyCode::LShift), Binding::Gamepad(GamepadButton::RightStick)]),
+            (Action::Break, vec![Binding::Mouse(MouseButton::Left), Binding::GamepadAxis(GamepadAxis::RightTrigger, AxisDirection::Positive)]),
+            (Action::Place, vec![Binding::Mouse(MouseButton::Right), Binding::GamepadAxis(GamepadAxis::LeftTrigger, AxisDirection::Positive)]),
+            (Action::ToggleCursor, vec![Binding::Key(VirtualKeyCode::LAlt), Binding::Gamepad(GamepadButton::Start)]),
+            (Action::ToggleSpectator, vec![Binding::Key(VirtualKeyCode::F), Binding::Gamepad(GamepadButton::DPadUp)]),
+            (Action::ToggleProjection, vec![Binding::Key(VirtualKeyCode::F4)]),
+            (Action::ToggleFullscreen, vec![Binding::Key(VirtualKeyCode::F11)]),
+            (Action::Quit, vec![Binding::Key(VirtualKeyCode::Escape)]),
+            (Action::LookUp, vec![Binding::GamepadAxis(GamepadAxis::RightStickY, AxisDirection::Positive)]),
+            (Action::LookDown, vec![Binding::GamepadAxis(GamepadAxis::RightStickY, AxisDirection::Negative)]),
+            (Action::LookLeft, vec![Binding::GamepadAxis(GamepadAxis::RightStickX, AxisDirection::Negative)]),
+            (Action::LookRight, vec![Binding::GamepadAxis(GamepadAxis::RightStickX, AxisDirection::Positive)])
+        ];
+
+        Bindings {
+            actions: defaults.into_iter().collect()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Bindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bindings, D::Error> {
+        let overrides: HashMap<Action, Vec<Binding>> = HashMap::deserialize(deserializer)?;
+        let mut bindings = Bindings::default();
+
+        bindings.actions.extend(overrides);
+
+        Ok(bindings)
+    }
+}
+
+// Collects device events as they arrive and resolves them into action states once per tick,
+// so gameplay sees the same presses and releases no matter how the events were spread across frames
+pub struct Input {
+    bindings: Bindings,
+    deadzone: f32,
+    // Keys and mouse buttons, controllers keep their own in `gamepads`
+    down: HashSet<Binding>,
+    gamepads: HashMap<GamepadId, GamepadState>,
+    // Bindings that went down since the last update, even if they have been released again
+    tapped: HashSet<Binding>,
+    values: HashMap<Action, f32>,
+    just_pressed: HashSet<Action>,
+    just_released: HashSet<Action>
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Input {
+        Input {
+            bindings: bindings,
+            deadzone: 0.2,
+            down: HashSet::new(),
+            gamepads: HashMap::new(),
+            tapped: HashSet::new(),
+            values: HashMap::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new()
+        }
+    }
+
+    // Fraction of an axis' travel that is ignored, to hide stick drift and resting triggers
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.95);
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent<'_>) {
+        match *event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(key) = input.virtual_keycode {
+                    self.set_binding(Binding::Key(key), input.state == ElementState::Pressed);
+                }
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.set_binding(Binding::Mouse(button), state == ElementState::Pressed);
+            },
+            _ => {}
+        }
+    }
+
+    #[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+    pub fn handle_gamepad_event(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Button(id, button, down) => {
+                let buttons = &mut self.gamepads.entry(id).or_default().buttons;
+
+                if !down {
+                    buttons.remove(&button);
+                } else if buttons.insert(button) {
+                    self.tapped.insert(Binding::Gamepad(button));
+                }
+            },
+            GamepadEvent::Axis(id, axis, value) => {
+                self.gamepads.entry(id).or_default().axes.insert(axis, value.clamp(-1.0, 1.0));
+            },
+            GamepadEvent::Disconnected(id) => {
+                self.gamepads.remove(&id);
+            }
+        }
+    }
+
+    pub fn set_binding(&mut self, binding: Binding, down: bool) {
+        if !down {
+            self.down.remove(&binding);
+            return;
+        }
+
+        // Key repeat sends more presses for a key that is already down
+        if self.down.insert(binding) {
+            self.tapped.insert(binding);
+        }
+    }
+
+    // Resolve everything that happened since the last tick, call once at the start of every tick
+    pub fn update(&mut self) {
+        let mut values = HashMap::new();
+
+        self.just_pressed.clear();
+        self.just_released.clear();
+
+        for (action, bindings) in self.bindings.actions.iter() {
+            let value = bindings.iter()
+                .map(|binding| self.binding_value(binding))
+                .fold(0.0, f32::max);
+
+            let was_held = self.values.get(action).copied().unwrap_or(0.0) >= PRESS_THRESHOLD;
+            let held = value >= PRESS_THRESHOLD;
+            let tapped = bindings.iter().any(|binding| self.tapped.contains(binding));
+
+            // A tap that went down and up between two ticks still counts as both edges
+            if (!was_held && (held || tapped)) {
+                self.just_pressed.insert(*action);
+            }
+
+            if (!held && (was_held || tapped)) {
+                self.just_released.insert(*action);
+            }
+
+            values.insert(*action, value);
+        }
+
+        self.values = values;
+        self.tapped.clear();
+    }
+
+    // Raw axis value on one controller with the deadzone removed and the rest rescaled to the full range
+    fn axis_value(&self, gamepad: &GamepadState, axis: GamepadAxis) -> f32 {
+        let raw = |axis: GamepadAxis| *gamepad.axes.get(&axis).unwrap_or(&0.0);
+        let value = raw(axis);
+
+        // Sticks use a radial deadzone so diagonals aren't snapped to the nearest axis
+        let magnitude = match axis.partner() {
+            Some(partner) => value.hypot(raw(partner)),
+            None => value.abs()
+        };
+
+        if magnitude <= self.deadzone {
+            return 0.0;
+        }
+
+        let scaled = (magnitude.min(1.0) - self.deadzone) / (1.0 - self.deadzone);
+
+        return value / magnitude * scaled;
+    }
+
+    fn binding_value(&self, binding: &Binding) -> f32 {
+        match binding {
+            // Whichever controller pushes furthest in the bound direction
+            Binding::GamepadAxis(axis, direction) => self.gamepads.values().map(|gamepad| {
+                let value = self.axis_value(gamepad, *axis);
+
+                match direction {
+                    AxisDirection::Positive => value.max(0.0),
+                    AxisDirection::Negative => (-value).max(0.0)
+                }
+            }).fold(0.0, f32::max),
+            Binding::Gamepad(button) => {
+                if self.gamepads.values().any(|gamepad| gamepad.buttons.contains(button)) { 1.0 } else { 0.0 }
+            },
+            _ => if self.down.contains(binding) { 1.0 } else { 0.0 }
+        }
+    }
+
+    // How strongly an action was held at the last update, from 0.0 to 1.0; buttons are always all or nothing
+    pub fn value(&self, action: Action) -> f32 {
+        return self.values.get(&action).copied().unwrap_or(0.0);
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        return self.value(action) >= PRESS_THRESHOLD;
+    }
+
+    // Went down since the previous tick
+    pub fn just_pressed(&self, action: Action) -> bool {
+        return self.just_pressed.contains(&action);
+    }
+
+    // Went up since the previous tick
+    #[allow(dead_code)]
+    pub fn just_released(&self, action: Action) -> bool {
+        return self.just_released.contains(&action);
+    }
+
+    // -1.0..=1.0 from two opposing actions, analog bindings give the values in between
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        return (self.value(positive) - self.value(negative)).clamp(-1.0, 1.0);
+    }
+
+    // Let go of the keyboard and mouse, e.g. when the window loses focus and stops seeing their
+    // release events. Actions that were held report a release on the next update. Controllers are
+    // read whether or not the window has focus, and only report changes, so they are kept
+    pub fn clear(&mut self) {
+        self.down.clear();
+        self.tapped.retain(|binding| matches!(binding, Binding::Gamepad(_)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::glutin::event::{DeviceId, KeyboardInput, ModifiersState};
+
+    #[allow(deprecated)]
+    fn key_event(key: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state: state,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState::empty()
+            },
+            is_synthetic: true
+        }
+    }
+
+    #[allow(deprecated)]
+    fn mouse_event(button: MouseButton, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state: state,
+            button: button,
+            modifiers: ModifiersState::empty()
+        }
+    }
+
+    #[test]
+    fn key_events_drive_held_actions() {
+        let mut input = Input::new(Bindings::default());
+
+        input.handle_window_event(&key_event(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(!input.is_held(Action::MoveForward));
+
+        input.update();
+        assert!(input.is_held(Action::MoveForward));
+        assert!(input.just_pressed(Action::MoveForward));
+        assert_eq!(input.axis(Action::MoveBackward, Action::MoveForward), 1.0);
+
+        input.update();
+        assert!(input.is_held(Action::MoveForward));
+        assert!(!input.just_pressed(Action::MoveForward));
+
+        input.handle_window_event(&key_event(VirtualKeyCode::W, ElementState::Released));
+        input.update();
+        assert!(!input.is_held(Action::MoveForward));
+        assert!(input.just_released(Action::MoveForward));
+    }
+
+    #[test]
+    fn any_of_several_bindings_triggers_the_action() {
+        let mut bindings = Bindings::default();
+        bindings.actions.insert(Action::Jump, vec![Binding::Key(VirtualKeyCode::Space), Binding::Mouse(MouseButton::Middle)]);
+
+        let mut input = Input::new(bindings);
+
+        input.handle_window_event(&mouse_event(MouseButton::Middle, ElementState::Pressed));
+        input.update();
+        assert!(input.just_pressed(Action::Jump));
+
+        // Swapping from one binding to the other keeps the action held without a new edge
+        input.handle_window_event(&key_event(VirtualKeyCode::Space, ElementState::Pressed));
+        input.handle_window_event(&mouse_event(MouseButton::Middle, ElementState::Released));
+        input.update();
+        assert!(input.is_held(Action::Jump));
+        assert!(!input.just_pressed(Action::Jump));
+        assert!(!input.just_released(Action::Jump));
+    }
+
+    #[test]
+    fn key_repeat_does_not_press_again() {
+        let mut input = Input::new(Bindings::default());
+
+        input.handle_window_event(&key_event(VirtualKeyCode::LAlt, ElementState::Pressed));
+        input.update();
+        assert!(input.just_pressed(Action::ToggleCursor));
+
+        input.handle_window_event(&key_event(VirtualKeyCode::LAlt, ElementState::Pressed));
+        input.update();
+        assert!(!input.just_pressed(Action::ToggleCursor));
+        assert!(input.is_held(Action::ToggleCursor));
+    }
+
+    #[test]
+    fn tap_between_ticks_is_not_lost() {
+        let mut input = Input::new(Bindings::default());
+
+        input.handle_window_event(&mouse_event(MouseButton::Left, ElementState::Pressed));
+        input.handle_window_event(&mouse_event(MouseButton::Left, ElementState::Released));
+        input.update();
+
+        assert!(input.just_pressed(Action::Break));
+        assert!(input.just_released(Action::Break));
+        assert!(!input.is_held(Action::Break));
+
+        input.update();
+        assert!(!input.just_pressed(Action::Break));
+    }
+
+    #[test]
+    fn config_overrides_only_the_listed_actions() {
+        let bindings: Bindings = serde_json::from_str(r#"{ "Jump": [{ "Key": "J" }, { "Mouse": "Right" }] }"#).unwrap();
+
+        assert_eq!(bindings.actions[&Action::Jump], vec![Binding::Key(VirtualKeyCode::J), Binding::Mouse(MouseButton::Right)]);
+        assert_eq!(bindings.actions[&Action::MoveForward], Bindings::default().actions[&Action::MoveForward]);
+    }
+
+    #[test]
+    fn bindings_read_back_what_they_save() {
+        let json = serde_json::to_string(&Bindings::default()).unwrap();
+        let bindings: Bindings = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(bindings, Bindings::default());
+    }
+
+    #[test]
+    fn clear_releases_everything() {
+        let mut input = Input::new(Bindings::default());
+
+        input.set_binding(Binding::Key(VirtualKeyCode::D), true);
+        input.update();
+        input.clear();
+        input.update();
+
+        assert!(!input.is_held(Action::MoveRight));
+        assert!(input.just_released(Action::MoveRight));
+    }
+
+    #[test]
+    fn clear_keeps_gamepads_held() {
+        let mut input = Input::new(Bindings::default());
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::LeftStickY, 1.0));
+        input.handle_gamepad_event(GamepadEvent::Button(0, GamepadButton::South, true));
+        input.update();
+        input.clear();
+        input.update();
+
+        // The stick isn't moved again, so nothing would bring it back if it were dropped
+        assert_eq!(input.axis(Action::MoveBackward, Action::MoveForward), 1.0);
+        assert!(input.is_held(Action::Jump));
+        assert!(!input.just_released(Action::Jump));
+    }
+
+    #[test]
+    fn sticks_give_analog_movement() {
+        let mut input = Input::new(Bindings::default());
+        input.set_deadzone(0.2);
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::LeftStickY, 0.6));
+        input.update();
+
+        let forward = input.axis(Action::MoveBackward, Action::MoveForward);
+        assert!((forward - 0.5).abs() < 1e-5);
+        assert!(input.is_held(Action::MoveForward));
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::LeftStickY, -1.0));
+        input.update();
+        assert_eq!(input.axis(Action::MoveBackward, Action::MoveForward), -1.0);
+    }
+
+    #[test]
+    fn deadzone_hides_stick_drift() {
+        let mut input = Input::new(Bindings::default());
+        input.set_deadzone(0.2);
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::RightStickX, 0.1));
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::RightStickY, -0.1));
+        input.update();
+
+        assert_eq!(input.axis(Action::LookLeft, Action::LookRight), 0.0);
+        assert_eq!(input.axis(Action::LookDown, Action::LookUp), 0.0);
+
+        // Past the deadzone both axes of the stick come through, even the small one
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::RightStickX, 0.8));
+        input.update();
+        assert!(input.axis(Action::LookLeft, Action::LookRight) > 0.5);
+        assert!(input.axis(Action::LookDown, Action::LookUp) < 0.0);
+    }
+
+    #[test]
+    fn trigger_pull_presses_once() {
+        let mut input = Input::new(Bindings::default());
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::RightTrigger, 0.3));
+        input.update();
+        assert!(!input.just_pressed(Action::Break));
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::RightTrigger, 0.9));
+        input.update();
+        assert!(input.just_pressed(Action::Break));
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::RightTrigger, 1.0));
+        input.update();
+        assert!(!input.just_pressed(Action::Break));
+        assert!(input.is_held(Action::Break));
+
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::RightTrigger, 0.0));
+        input.update();
+        assert!(input.just_released(Action::Break));
+    }
+
+    #[test]
+    fn disconnect_releases_that_gamepad_only() {
+        let mut input = Input::new(Bindings::default());
+
+        input.handle_gamepad_event(GamepadEvent::Button(0, GamepadButton::South, true));
+        input.handle_gamepad_event(GamepadEvent::Axis(0, GamepadAxis::LeftStickX, 1.0));
+        input.handle_gamepad_event(GamepadEvent::Button(1, GamepadButton::Start, true));
+        input.handle_gamepad_event(GamepadEvent::Axis(1, GamepadAxis::LeftStickY, 1.0));
+        input.set_binding(Binding::Key(VirtualKeyCode::W), true);
+        input.update();
+
+        input.handle_gamepad_event(GamepadEvent::Disconnected(0));
+        input.update();
+
+        assert!(input.just_released(Action::Jump));
+        assert_eq!(input.axis(Action::MoveLeft, Action::MoveRight), 0.0);
+        assert!(input.is_held(Action::ToggleCursor));
+        assert!(input.is_held(Action::MoveForward));
+
+        // The key keeps holding what the second pad's stick was also pushing
+        input.handle_gamepad_event(GamepadEvent::Disconnected(1));
+        input.update();
+
+        assert!(input.just_released(Action::ToggleCursor));
+        assert!(input.is_held(Action::MoveForward));
+    }
+
+    #[test]
+    fn buttons_on_either_gamepad_hold_the_action() {
+        let mut input = Input::new(Bindings::default());
+
+        input.handle_gamepad_event(GamepadEvent::Button(0, GamepadButton::South, true));
+        input.handle_gamepad_event(GamepadEvent::Button(1, GamepadButton::South, true));
+        input.update();
+        assert!(input.just_pressed(Action::Jump));
+
+        input.handle_gamepad_event(GamepadEvent::Button(0, GamepadButton::South, false));
+        input.update();
+        assert!(input.is_held(Action::Jump));
+
+        input.handle_gamepad_event(GamepadEvent::Button(1, GamepadButton::South, false));
+        input.update();
+        assert!(input.just_released(Action::Jump));
+    }
+}
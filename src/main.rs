@@ -1,4 +1,6 @@
 #![allow(unused_parens)]
+// The codebase writes out `return` and repeats field names in struct literals on purpose
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
 
 extern crate glium;
 extern crate image;
@@ -7,24 +9,54 @@ extern crate nalgebra;
 
 use glium::glutin;
 
+mod assets;
+#[cfg(feature = "hot-reload")]
+mod hotreload;
 mod textures;
 mod shaders;
 mod transform;
 mod cube;
+mod biome;
 mod blockbuilder;
 mod models;
 mod meshbuilder;
 mod camera;
+mod clipcontrol;
+mod chunk;
+mod world;
+mod physics;
+mod player;
+mod input;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod settings;
+mod daytime;
+mod console;
+mod clouds;
+mod shadows;
+mod postprocess;
+mod save;
 mod game;
 
 fn main() {
     let event_loop = glutin::event_loop::EventLoop::new();
     let mut active_game = game::Game::new();
-    active_game.create_window(&event_loop, "┬─┬ ノ( ゜-゜ノ)", 1280, 720, false, true);
-    active_game.load_textures();
-    active_game.load_shaders();
-    active_game.load_models();
-    active_game.load_blocks();
-    active_game.ready();
+
+    if let Err(error) = load(&mut active_game, &event_loop) {
+        eprintln!("Failed to start: {}", error);
+        std::process::exit(1);
+    }
+
     active_game.start_loop(event_loop);
 }
+
+fn load(active_game: &mut game::Game, event_loop: &glutin::event_loop::EventLoop<()>) -> Result<(), Box<dyn std::error::Error>> {
+    active_game.create_window(event_loop, "┬─┬ ノ( ゜-゜ノ)")?;
+    active_game.load_textures()?;
+    active_game.load_shaders()?;
+    active_game.load_models()?;
+    active_game.load_blocks()?;
+    active_game.ready()?;
+
+    return Ok(());
+}
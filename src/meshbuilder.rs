@@ -3,8 +3,8 @@ use crate::{models, cube};
 pub struct Mesh {
     pub vertices: Option<glium::vertex::VertexBuffer<models::Vertex>>,
     pub indices: Option<glium::index::IndexBuffer<u32>>,
-    pub shader: Option<glium::Program>,
-    pub texture: Option<glium::texture::SrgbTexture2d>
+    pub shader: Option<String>,
+    pub texture: Option<String>
 }
 
 pub struct MeshBuilder {
@@ -22,12 +22,12 @@ impl Mesh {
         self.indices = Some(indices);
     }
 
-    pub fn set_shader(&mut self, shader: glium::Program) {
-        self.shader = Some(shader);
+    pub fn set_shader(&mut self, shader: &str) {
+        self.shader = Some(shader.to_string());
     }
 
-    pub fn set_texture(&mut self, texture: glium::texture::SrgbTexture2d) {
-        self.texture = Some(texture);
+    pub fn set_texture(&mut self, texture: &str) {
+        self.texture = Some(texture.to_string());
     }
 }
 
@@ -40,11 +40,11 @@ impl MeshBuilder {
         }
     }
 
-    pub fn set_shader(&mut self, shader: glium::Program) {
+    pub fn set_shader(&mut self, shader: &str) {
         self.mesh.set_shader(shader);
     }
 
-    pub fn set_texture(&mut self, texture: glium::texture::SrgbTexture2d) {
+    pub fn set_texture(&mut self, texture: &str) {
         self.mesh.set_texture(texture);
     }
 
@@ -70,6 +70,11 @@ impl MeshBuilder {
         self.indices.extend_from_slice(&indices);
     }
 
+    pub fn add_face(&mut self, face: cube::Face) {
+        self.add(face.vertices, face.indices);
+    }
+
+    #[allow(dead_code)]
     pub fn add_cube(&mut self, mut cube: cube::Cube) {
         for (_face_type, face) in cube.faces.iter_mut() {
             self.add(face.vertices.clone(), face.indices.clone());
@@ -2,18 +2,49 @@ extern crate serde_json;
 extern crate obj;
 
 use std::{collections::HashMap};
-use include_dir::{include_dir, Dir};
+use std::fmt;
+
+use crate::assets;
 
 #[derive(Copy, Clone)]
 pub struct Vertex {
     pub position: (f32, f32, f32),
     pub normal: (f32, f32, f32),
-    pub tex_coords: [f32; 2]
+    pub tex_coords: [f32; 2],
+    // Sky and block light from 0 to 1
+    pub light: [f32; 2],
+    // Ambient occlusion, 0 for a fully enclosed corner and 1 for an open one
+    pub occlusion: f32,
+    // Multiplies the texture colour
+    pub color: [f32; 3]
 }
 
-glium::implement_vertex!(Vertex, position, normal, tex_coords);
+glium::implement_vertex!(Vertex, position, normal, tex_coords, light, occlusion, color);
+
+// Models that aren't part of the world are drawn in full daylight
+pub const FULL_LIGHT: [f32; 2] = [1.0, 0.0];
+
+// Leaves the texture as it is
+pub const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+#[derive(Debug)]
+pub enum ModelError {
+    NotFound(String),
+    Parse { file: String, source: obj::ObjError },
+    NotLoaded(String)
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::NotFound(file) => write!(f, "models/{}: file not found", file),
+            ModelError::Parse { file, source } => write!(f, "models/{}: {}", file, source),
+            ModelError::NotLoaded(name) => write!(f, "model '{}' was never loaded", name)
+        }
+    }
+}
 
-static MODELS: Dir<'_> = include_dir!("models");
+impl std::error::Error for ModelError {}
 
 #[derive(Clone)]
 pub struct Model {
@@ -46,32 +77,36 @@ impl Models {
         }
     }
 
-    pub fn load_model(&mut self, file_name: &str) -> Result<bool, String> {
+    pub fn load_model(&mut self, assets: &assets::Assets, file_name: &str) -> Result<(), ModelError> {
         let model_name = file_name.to_owned() + ".obj";
 
-        let model_source = MODELS.get_file(model_name.to_owned()).unwrap();
-        let loaded_model: obj::Obj<obj::TexturedVertex, u32> = obj::load_obj(model_source.contents()).unwrap();
+        let model_source = assets.read(&format!("models/{}", model_name)).ok_or_else(|| ModelError::NotFound(model_name.clone()))?;
+        let loaded_model: obj::Obj<obj::TexturedVertex, u32> = obj::load_obj(model_source.as_ref())
+            .map_err(|error| ModelError::Parse { file: model_name.clone(), source: error })?;
 
         let vertices: Vec<Vertex> = loaded_model.vertices.iter().map(|v: &obj::TexturedVertex| {
             Vertex {
                 position: (v.position[0], v.position[1], v.position[2]),
                 normal: (v.normal[0], v.normal[1], v.normal[2]),
-                tex_coords: [v.texture[0], v.texture[1]]
+                tex_coords: [v.texture[0], v.texture[1]],
+                light: FULL_LIGHT,
+                occlusion: 1.0,
+                color: WHITE
             }
         }).collect();
 
         self.loaded_models.insert(file_name.to_owned(), Model { vertices: vertices, indices: loaded_model.indices });
 
-        Ok(true)
+        Ok(())
     }
 
-    pub fn get_model(&mut self, model_name: &str) -> Result<Model, String> {
+    pub fn get_model(&mut self, model_name: &str) -> Result<Model, ModelError> {
         match self.loaded_models.get(model_name) {
             Some(model) => {
                 Ok(model.clone())
             },
             None => {
-                Err("Model not found".to_owned())
+                Err(ModelError::NotLoaded(model_name.to_owned()))
             }
         }
     }
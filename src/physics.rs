@@ -0,0 +1,271 @@
+use crate::chunk;
+use crate::world;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: nalgebra::Vector3<f32>,
+    pub max: nalgebra::Vector3<f32>
+}
+
+impl Aabb {
+    // A box standing on `feet`, centered horizontally
+    pub fn from_feet(feet: nalgebra::Point3<f32>, width: f32, height: f32) -> Aabb {
+        let half_width = width / 2.0;
+
+        Aabb {
+            min: nalgebra::Vector3::new(feet.x - half_width, feet.y, feet.z - half_width),
+            max: nalgebra::Vector3::new(feet.x + half_width, feet.y + height, feet.z + half_width)
+        }
+    }
+
+    pub fn translate(&self, offset: nalgebra::Vector3<f32>) -> Aabb {
+        Aabb {
+            min: self.min + offset,
+            max: self.max + offset
+        }
+    }
+
+    // Grow the box along a motion so it covers everything it could touch on the way
+    pub fn expand(&self, motion: nalgebra::Vector3<f32>) -> Aabb {
+        Aabb {
+            min: self.min + motion.inf(&nalgebra::Vector3::zeros()),
+            max: self.max + motion.sup(&nalgebra::Vector3::zeros())
+        }
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis])
+    }
+
+    pub fn from_block(x: i32, y: i32, z: i32) -> Aabb {
+        Aabb {
+            min: nalgebra::Vector3::new(x as f32, y as f32, z as f32),
+            max: nalgebra::Vector3::new(x as f32 + 1.0, y as f32 + 1.0, z as f32 + 1.0)
+        }
+    }
+}
+
+// Tiny gap kept between a box and the voxel it stops against, so it doesn't start the next move inside it
+const SKIN: f32 = 1e-4;
+
+pub struct SweepResult {
+    pub motion: nalgebra::Vector3<f32>,
+    pub collided: [bool; 3]
+}
+
+// Move an AABB through the voxel grid, clipping the motion against solid blocks one axis at a time (Y, X, then Z)
+pub fn sweep(world: &world::World, aabb: &Aabb, motion: nalgebra::Vector3<f32>) -> SweepResult {
+    let mut current = *aabb;
+    let mut result = SweepResult {
+        motion: nalgebra::Vector3::zeros(),
+        collided: [false; 3]
+    };
+
+    for axis in [1, 0, 2] {
+        let wanted = motion[axis];
+
+        if wanted == 0.0 {
+            continue;
+        }
+
+        let mut axis_motion = nalgebra::Vector3::zeros();
+        axis_motion[axis] = wanted;
+
+        let region = current.expand(axis_motion);
+        let mut allowed = wanted;
+
+        for x in region.min.x.floor() as i32..region.max.x.ceil() as i32 {
+            for y in region.min.y.floor() as i32..region.max.y.ceil() as i32 {
+                for z in region.min.z.floor() as i32..region.max.z.ceil() as i32 {
+                    if !world.is_solid(x, y, z) {
+                        continue;
+                    }
+
+                    let block = Aabb::from_block(x, y, z);
+
+                    allowed = clip_axis(&current, &block, axis, allowed);
+                }
+            }
+        }
+
+        if allowed != wanted {
+            result.collided[axis] = true;
+        }
+
+        let mut applied = nalgebra::Vector3::zeros();
+        applied[axis] = allowed;
+
+        current = current.translate(applied);
+        result.motion[axis] = allowed;
+    }
+
+    return result;
+}
+
+// Shorten a motion along one axis so `moving` stops at the face of `block`
+fn clip_axis(moving: &Aabb, block: &Aabb, axis: usize, motion: f32) -> f32 {
+    // Only blocks overlapping on the other two axes can be hit
+    for other in 0..3 {
+        if other != axis && (moving.max[other] <= block.min[other] || moving.min[other] >= block.max[other]) {
+            return motion;
+        }
+    }
+
+    if motion > 0.0 && moving.max[axis] <= block.min[axis] {
+        return motion.min((block.min[axis] - moving.max[axis] - SKIN).max(0.0));
+    }
+
+    if motion < 0.0 && moving.min[axis] >= block.max[axis] {
+        return motion.max((block.max[axis] - moving.min[axis] + SKIN).min(0.0));
+    }
+
+    return motion;
+}
+
+pub struct RayHit {
+    pub block: (i32, i32, i32),
+    // Face of the block the ray entered through, zero if the ray started inside it
+    pub normal: (i32, i32, i32)
+}
+
+// Walk the voxel grid along a ray and return the first block within reach that isn't air
+pub fn raycast(world: &world::World, origin: nalgebra::Point3<f32>, direction: nalgebra::Vector3<f32>, max_distance: f32) -> Option<RayHit> {
+    let direction = direction.try_normalize(f32::EPSILON)?;
+
+    let mut block = [origin.x.floor() as i32, origin.y.floor() as i32, origin.z.floor() as i32];
+    let mut normal = [0, 0, 0];
+    let mut step = [0, 0, 0];
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            t_delta[axis] = 1.0 / direction[axis];
+            t_max[axis] = (block[axis] as f32 + 1.0 - origin[axis]) * t_delta[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            t_delta[axis] = -1.0 / direction[axis];
+            t_max[axis] = (origin[axis] - block[axis] as f32) * t_delta[axis];
+        }
+    }
+
+    loop {
+        // Blocks the player can move through can still be aimed at
+        if world.get_block(block[0], block[1], block[2]) != chunk::AIR {
+            return Some(RayHit {
+                block: (block[0], block[1], block[2]),
+                normal: (normal[0], normal[1], normal[2])
+            });
+        }
+
+        let axis = if t_max[0] < t_max[1] {
+            if t_max[0] < t_max[2] { 0 } else { 2 }
+        } else if t_max[1] < t_max[2] { 1 } else { 2 };
+
+        if t_max[axis] > max_distance {
+            return None;
+        }
+
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+
+        normal = [0, 0, 0];
+        normal[axis] = -step[axis];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockbuilder;
+    use crate::cube;
+
+    fn floor_world() -> world::World {
+        let mut test_world = world::World::new(0);
+        let dirt = test_world.block_id("dirt");
+
+        for x in -2..=2 {
+            for z in -2..=2 {
+                test_world.set_block(x, 0, z, dirt);
+            }
+        }
+
+        test_world
+    }
+
+    #[test]
+    fn falling_box_lands_on_floor() {
+        let test_world = floor_world();
+        let aabb = Aabb::from_feet(nalgebra::Point3::new(0.5, 3.0, 0.5), 0.6, 1.8);
+
+        let result = sweep(&test_world, &aabb, nalgebra::Vector3::new(0.0, -5.0, 0.0));
+
+        assert!(result.collided[1]);
+        assert!((aabb.min.y + result.motion.y - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn wall_stops_horizontal_motion_but_not_vertical() {
+        let mut test_world = floor_world();
+        let dirt = test_world.block_id("dirt");
+        test_world.set_block(2, 1, 0, dirt);
+        test_world.set_block(2, 2, 0, dirt);
+
+        let aabb = Aabb::from_feet(nalgebra::Point3::new(1.5, 1.0, 0.5), 0.6, 1.8);
+        let result = sweep(&test_world, &aabb, nalgebra::Vector3::new(1.0, 0.0, 0.0));
+
+        assert!(result.collided[0]);
+        assert!(aabb.max.x + result.motion.x <= 2.0);
+        assert!(!result.collided[1]);
+    }
+
+    #[test]
+    fn raycast_hits_floor_from_above() {
+        let test_world = floor_world();
+
+        let hit = raycast(&test_world, nalgebra::Point3::new(0.5, 2.6, 0.5), nalgebra::Vector3::new(0.5, -1.0, 0.0), 5.0).unwrap();
+
+        assert_eq!(hit.block, (1, 0, 0));
+        assert_eq!(hit.normal, (0, 1, 0));
+    }
+
+    #[test]
+    fn water_is_passed_through_but_can_be_aimed_at() {
+        let mut blocks = std::collections::HashMap::new();
+        blockbuilder::BlockBuilder::new().set_name("water").set_transparent(true).set_solid(false).add_cube(cube::Cube::new()).build(Some(&mut blocks));
+
+        let mut test_world = floor_world();
+        test_world.set_block_properties(&blocks);
+
+        let water = test_world.block_id("water");
+        test_world.set_block(0, 1, 0, water);
+
+        let aabb = Aabb::from_feet(nalgebra::Point3::new(0.5, 3.0, 0.5), 0.6, 1.8);
+        let result = sweep(&test_world, &aabb, nalgebra::Vector3::new(0.0, -5.0, 0.0));
+
+        // Through the water and onto the floor under it
+        assert!((aabb.min.y + result.motion.y - 1.0).abs() < 1e-3);
+
+        let hit = raycast(&test_world, nalgebra::Point3::new(0.5, 3.5, 0.5), nalgebra::Vector3::new(0.0, -1.0, 0.0), 5.0).unwrap();
+
+        assert_eq!(hit.block, (0, 1, 0));
+    }
+
+    #[test]
+    fn raycast_stops_at_max_distance() {
+        let test_world = floor_world();
+
+        assert!(raycast(&test_world, nalgebra::Point3::new(0.5, 10.0, 0.5), nalgebra::Vector3::new(0.0, -1.0, 0.0), 5.0).is_none());
+    }
+
+    #[test]
+    fn fast_motion_does_not_tunnel_through_thin_floor() {
+        let test_world = floor_world();
+        let aabb = Aabb::from_feet(nalgebra::Point3::new(0.5, 20.0, 0.5), 0.6, 1.8);
+
+        let result = sweep(&test_world, &aabb, nalgebra::Vector3::new(0.0, -100.0, 0.0));
+
+        assert!(aabb.min.y + result.motion.y >= 1.0 - 1e-3);
+    }
+}
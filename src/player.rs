@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::physics;
+use crate::transform;
+use crate::world;
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+pub const EYE_HEIGHT: f32 = 1.62;
+
+// Speeds are in blocks per second, accelerations in blocks per second squared
+const WALK_SPEED: f32 = 4.3;
+const SNEAK_SPEED: f32 = 1.3;
+const SPECTATOR_SPEED: f32 = 24.0;
+const GRAVITY: f32 = 32.0;
+const TERMINAL_VELOCITY: f32 = 78.0;
+const JUMP_VELOCITY: f32 = 9.0;
+
+// Falling below this height puts the player back at spawn
+const VOID_HEIGHT: f32 = -64.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MovementMode {
+    Walking,
+    Spectator
+}
+
+// Movement requested for a single tick, each axis in -1.0..=1.0
+#[derive(Default)]
+pub struct PlayerInput {
+    pub forward: f32,
+    pub strafe: f32,
+    pub vertical: f32,
+    pub jump: bool,
+    pub sneak: bool
+}
+
+pub struct Player {
+    position: nalgebra::Point3<f32>,
+    velocity: nalgebra::Vector3<f32>,
+    spawn: nalgebra::Point3<f32>,
+    on_ground: bool,
+    mode: MovementMode
+}
+
+impl Player {
+    pub fn new(spawn: nalgebra::Point3<f32>) -> Player {
+        Player {
+            position: spawn,
+            velocity: nalgebra::Vector3::zeros(),
+            spawn: spawn,
+            on_ground: false,
+            mode: MovementMode::Walking
+        }
+    }
+
+    // Position of the player's feet
+    pub fn get_position(&self) -> nalgebra::Point3<f32> {
+        return self.position;
+    }
+
+    pub fn set_position(&mut self, position: nalgebra::Point3<f32>) {
+        self.position = position;
+        self.velocity = nalgebra::Vector3::zeros();
+    }
+
+    pub fn get_spawn(&self) -> nalgebra::Point3<f32> {
+        return self.spawn;
+    }
+
+    pub fn eye_position(&self) -> nalgebra::Point3<f32> {
+        return self.position + nalgebra::Vector3::new(0.0, EYE_HEIGHT, 0.0);
+    }
+
+    pub fn get_mode(&self) -> MovementMode {
+        return self.mode;
+    }
+
+    pub fn set_mode(&mut self, mode: MovementMode) {
+        if (self.mode != mode) {
+            self.toggle_mode();
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            MovementMode::Walking => MovementMode::Spectator,
+            MovementMode::Spectator => MovementMode::Walking
+        };
+
+        self.velocity = nalgebra::Vector3::zeros();
+        self.on_ground = false;
+    }
+
+    pub fn get_aabb(&self) -> physics::Aabb {
+        physics::Aabb::from_feet(self.position, PLAYER_WIDTH, PLAYER_HEIGHT)
+    }
+
+    // Advance the player by `delta` seconds, steering relative to where `look` is facing
+    pub fn tick(&mut self, input: &PlayerInput, look: &transform::Transform, world: &world::World, delta: f32) {
+        match self.mode {
+            MovementMode::Walking => self.tick_walking(input, look, world, delta),
+            MovementMode::Spectator => self.tick_spectator(input, look, delta)
+        }
+    }
+
+    fn tick_walking(&mut self, input: &PlayerInput, look: &transform::Transform, world: &world::World, delta: f32) {
+        // Walk along the ground plane no matter how far up or down the player is looking
+        let mut forward = look.forward();
+        forward.y = 0.0;
+        let forward = forward.try_normalize(f32::EPSILON).unwrap_or_else(nalgebra::Vector3::zeros);
+        let right = nalgebra::Vector3::new(-forward.z, 0.0, forward.x);
+
+        let mut wish = forward * input.forward + right * input.strafe;
+
+        if wish.norm_squared() > 1.0 {
+            wish = wish.normalize();
+        }
+
+        let speed = if input.sneak { SNEAK_SPEED } else { WALK_SPEED };
+
+        self.velocity.x = wish.x * speed;
+        self.velocity.z = wish.z * speed;
+
+        if (input.jump && self.on_ground) {
+            self.velocity.y = JUMP_VELOCITY;
+        }
+
+        self.velocity.y = (self.velocity.y - GRAVITY * delta).max(-TERMINAL_VELOCITY);
+
+        let result = physics::sweep(world, &self.get_aabb(), self.velocity * delta);
+
+        self.position += result.motion;
+        self.on_ground = result.collided[1] && self.velocity.y < 0.0;
+
+        for axis in 0..3 {
+            if result.collided[axis] {
+                self.velocity[axis] = 0.0;
+            }
+        }
+
+        if (self.position.y < VOID_HEIGHT) {
+            self.position = self.spawn;
+            self.velocity = nalgebra::Vector3::zeros();
+        }
+    }
+
+    fn tick_spectator(&mut self, input: &PlayerInput, look: &transform::Transform, delta: f32) {
+        let mut speed = SPECTATOR_SPEED;
+
+        if input.sneak {
+            speed /= 4.0;
+        }
+
+        let motion = look.forward() * input.forward + look.right() * input.strafe + nalgebra::Vector3::new(0.0, input.vertical, 0.0);
+
+        self.position += motion * speed * delta;
+    }
+}
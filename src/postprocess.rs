@@ -0,0 +1,195 @@
+use glium::Surface;
+use glium::CapabilitiesSource;
+use glium::backend::Facade;
+use glium::uniform;
+
+use crate::settings;
+
+// The scene is drawn in HDR so bright light survives until tonemapping
+const COLOR_FORMAT: glium::texture::UncompressedFloatFormat = glium::texture::UncompressedFloatFormat::F16F16F16F16;
+// Float depth, which reverse-Z needs to gain any precision
+const DEPTH_FORMAT: glium::texture::DepthFormat = glium::texture::DepthFormat::F32;
+
+// Every GL 3 driver supports at least this many samples, used when it won't say which counts work
+const GUARANTEED_SAMPLES: u32 = 4;
+
+struct Multisampled {
+    color: glium::texture::Texture2dMultisample,
+    depth: glium::texture::DepthTexture2dMultisample
+}
+
+// Offscreen targets the scene is drawn into, and the chain of effects that takes it to the screen
+pub struct PostProcess {
+    samples: u32,
+    // Where the scene is drawn with MSAA on, resolved into `scene` before the effects run
+    multisampled: Option<Multisampled>,
+    scene: glium::texture::Texture2d,
+    depth: glium::texture::DepthTexture2d,
+    // Effects read from one and write into the other
+    swap: [glium::texture::Texture2d; 2]
+}
+
+impl PostProcess {
+    pub fn new(display: &glium::Display, width: u32, height: u32, requested_samples: u16) -> Result<PostProcess, glium::texture::TextureCreationError> {
+        let capabilities = display.get_context().get_capabilities();
+
+        // A count is usable if both attachments can have it
+        let samples = choose_samples(requested_samples, |samples| {
+            let formats = [
+                glium::texture::TextureFormat::UncompressedFloat(COLOR_FORMAT),
+                glium::texture::TextureFormat::DepthFormat(DEPTH_FORMAT)
+            ];
+
+            formats.iter().all(|format| {
+                match capabilities.internal_formats_textures.get(format).and_then(|infos| infos.multisamples.as_ref()) {
+                    Some(counts) => counts.contains(&(samples as i32)),
+                    None => samples <= GUARANTEED_SAMPLES
+                }
+            })
+        });
+
+        return PostProcess::with_samples(display, width, height, samples);
+    }
+
+    fn with_samples(display: &glium::Display, width: u32, height: u32, samples: u32) -> Result<PostProcess, glium::texture::TextureCreationError> {
+        // A minimised window reports a size of 0
+        let (width, height) = (width.max(1), height.max(1));
+        let no_mipmaps = glium::texture::MipmapsOption::NoMipmap;
+
+        let multisampled = match samples {
+            0 => None,
+            samples => Some(Multisampled {
+                color: glium::texture::Texture2dMultisample::empty_with_format(display, COLOR_FORMAT, no_mipmaps, width, height, samples)?,
+                depth: glium::texture::DepthTexture2dMultisample::empty_with_format(display, DEPTH_FORMAT, no_mipmaps, width, height, samples)?
+            })
+        };
+
+        let color_texture = || glium::texture::Texture2d::empty_with_format(display, COLOR_FORMAT, no_mipmaps, width, height);
+
+        Ok(PostProcess {
+            samples: samples,
+            multisampled: multisampled,
+            scene: color_texture()?,
+            depth: glium::texture::DepthTexture2d::empty_with_format(display, DEPTH_FORMAT, no_mipmaps, width, height)?,
+            swap: [color_texture()?, color_texture()?]
+        })
+    }
+
+    // Samples per pixel the scene is drawn with, which may be fewer than the settings asked for
+    pub fn get_samples(&self) -> u32 {
+        return self.samples;
+    }
+
+    // Recreate the targets at the window's new size
+    pub fn resize(&mut self, display: &glium::Display, width: u32, height: u32) -> Result<(), glium::texture::TextureCreationError> {
+        *self = PostProcess::with_samples(display, width, height, self.samples)?;
+
+        return Ok(());
+    }
+
+    // Framebuffer to draw the scene into
+    pub fn scene_target<'a>(&'a self, display: &glium::Display) -> glium::framebuffer::SimpleFrameBuffer<'a> {
+        let target = match &self.multisampled {
+            Some(multisampled) => glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(display, &multisampled.color, &multisampled.depth),
+            None => glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(display, &self.scene, &self.depth)
+        };
+
+        return target.expect("Scene attachments are created together so always match");
+    }
+
+    // Run the effects over the scene, the last one drawing into `frame`
+    pub fn apply(&self, display: &glium::Display, frame: &mut glium::Frame, program: &glium::Program, effects: &[settings::PostEffect], exposure: f32, underwater: bool) {
+        if let Some(multisampled) = &self.multisampled {
+            let source = glium::framebuffer::SimpleFrameBuffer::new(display, &multisampled.color).unwrap();
+            let resolved = glium::framebuffer::SimpleFrameBuffer::new(display, &self.scene).unwrap();
+
+            source.fill(&resolved, glium::uniforms::MagnifySamplerFilter::Nearest);
+        }
+
+        // Nothing to run, show the scene as it is
+        if effects.is_empty() {
+            let source = glium::framebuffer::SimpleFrameBuffer::new(display, &self.scene).unwrap();
+            source.fill(frame, glium::uniforms::MagnifySamplerFilter::Nearest);
+            return;
+        }
+
+        let behavior = glium::uniforms::SamplerBehavior {
+            minify_filter: glium::uniforms::MinifySamplerFilter::Linear,
+            magnify_filter: glium::uniforms::MagnifySamplerFilter::Linear,
+            wrap_function: (glium::uniforms::SamplerWrapFunction::Clamp, glium::uniforms::SamplerWrapFunction::Clamp, glium::uniforms::SamplerWrapFunction::Clamp),
+            ..Default::default()
+        };
+
+        let (width, height) = self.scene.dimensions();
+        let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+
+        let mut input = &self.scene;
+
+        for (index, effect) in effects.iter().enumerate() {
+            // A single triangle, placed by the vertex shader
+            let vertices = glium::vertex::EmptyVertexAttributes { len: 3 };
+            let uniforms = uniform! {
+                scene: glium::uniforms::Sampler(input, behavior),
+                effect: effect_id(*effect),
+                texel: [1.0 / width as f32, 1.0 / height as f32],
+                exposure: exposure,
+                underwater: if underwater { 1.0f32 } else { 0.0 }
+            };
+
+            if index + 1 == effects.len() {
+                frame.draw(vertices, indices, program, &uniforms, &Default::default()).unwrap();
+            } else {
+                let output = &self.swap[index % 2];
+                let mut target = glium::framebuffer::SimpleFrameBuffer::new(display, output).unwrap();
+
+                target.draw(vertices, indices, program, &uniforms, &Default::default()).unwrap();
+                input = output;
+            }
+        }
+    }
+}
+
+// Matches the effect constants in post-frag.glsl
+fn effect_id(effect: settings::PostEffect) -> i32 {
+    match effect {
+        settings::PostEffect::Tonemap => 0,
+        settings::PostEffect::Gamma => 1,
+        settings::PostEffect::Fxaa => 2,
+        settings::PostEffect::Underwater => 3,
+        settings::PostEffect::Vignette => 4
+    }
+}
+
+// The requested count rounded down to a power of two, halved until `supported` accepts it, down to no MSAA
+fn choose_samples(requested: u16, supported: impl Fn(u32) -> bool) -> u32 {
+    let mut samples = match requested.min(16) {
+        0 => 0,
+        requested => 1 << (15 - requested.leading_zeros())
+    };
+
+    while samples > 1 && !supported(samples) {
+        samples /= 2;
+    }
+
+    // 1 sample is the same as none, without the cost of resolving
+    if samples == 1 {
+        samples = 0;
+    }
+
+    return samples;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_count_falls_back_to_what_is_supported() {
+        assert_eq!(choose_samples(8, |_| true), 8);
+        assert_eq!(choose_samples(6, |_| true), 4);
+        assert_eq!(choose_samples(64, |_| true), 16);
+        assert_eq!(choose_samples(16, |samples| samples <= 4), 4);
+        assert_eq!(choose_samples(4, |_| false), 0);
+        assert_eq!(choose_samples(0, |_| true), 0);
+    }
+}
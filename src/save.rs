@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk;
+use crate::daytime;
+use crate::player;
+
+// Version 2 stores a block palette in every region file, version 3 the time of day in the metadata
+pub const FORMAT_VERSION: u32 = 3;
+
+const METADATA_FILE: &str = "world.json";
+const REGION_DIRECTORY: &str = "regions";
+const REGION_MAGIC: &[u8; 4] = b"BGRN";
+
+// Chunks per region file along each axis
+const REGION_SIZE: i32 = 8;
+
+type RegionPosition = (i32, i32, i32);
+
+// Each step upgrades from the version at its index + 1 to the next one, so
+// METADATA_MIGRATIONS[0] takes version 1 to 2
+type MetadataMigration = fn(&mut serde_json::Value) -> Result<(), String>;
+type RegionMigration = fn(&mut Region, &[String]) -> Result<(), String>;
+
+const METADATA_MIGRATIONS: [MetadataMigration; FORMAT_VERSION as usize - 1] = [metadata_v1_to_v2, metadata_v2_to_v3];
+const REGION_MIGRATIONS: [RegionMigration; FORMAT_VERSION as usize - 1] = [region_v1_to_v2, region_v2_to_v3];
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io { path: PathBuf, source: io::Error },
+    Metadata { path: PathBuf, source: serde_json::Error },
+    Corrupt { path: PathBuf, reason: String },
+    TooNew { path: PathBuf, version: u32 }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SaveError::Metadata { path, source } => write!(f, "{}: {}", path.display(), source),
+            SaveError::Corrupt { path, reason } => write!(f, "{}: corrupt save file: {}", path.display(), reason),
+            SaveError::TooNew { path, version } => write!(f, "{}: saved by a newer version of the game (format {}, this build reads up to {})", path.display(), version, FORMAT_VERSION)
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerState {
+    // Feet position
+    pub position: [f32; 3],
+    // Pitch, yaw and roll of the view in radians
+    pub rotation: [f32; 3],
+    pub mode: player::MovementMode
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldMetadata {
+    pub format_version: u32,
+    pub seed: u32,
+    pub spawn: [f32; 3],
+    pub player: PlayerState,
+    // Fraction of the day since midnight
+    pub time_of_day: f32,
+    // Block names in id order, chunks store indices into this
+    pub palette: Vec<String>
+}
+
+// A world on disk: the metadata file next to a directory of region files
+pub struct WorldSave {
+    directory: PathBuf
+}
+
+impl WorldSave {
+    pub fn new(directory: &Path) -> WorldSave {
+        WorldSave {
+            directory: directory.to_path_buf()
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        return self.directory.join(METADATA_FILE).exists();
+    }
+
+    // Read the metadata, upgrading it from older formats
+    pub fn load_metadata(&self) -> Result<WorldMetadata, SaveError> {
+        let path = self.directory.join(METADATA_FILE);
+        let contents = fs::read(&path).map_err(|e| SaveError::Io { path: path.clone(), source: e })?;
+
+        let mut value: serde_json::Value = serde_json::from_slice(&contents).map_err(|e| SaveError::Metadata { path: path.clone(), source: e })?;
+
+        let version = match value.get("format_version").and_then(|version| version.as_u64()) {
+            Some(version) => version as u32,
+            None => return Err(SaveError::Corrupt { path: path, reason: "missing format version".to_string() })
+        };
+
+        check_version(&path, version)?;
+
+        for (index, migration) in METADATA_MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+            migration(&mut value).map_err(|reason| SaveError::Corrupt {
+                path: path.clone(),
+                reason: format!("could not upgrade from version {}: {}", index + 1, reason)
+            })?;
+        }
+
+        value["format_version"] = serde_json::Value::from(FORMAT_VERSION);
+
+        serde_json::from_value(value).map_err(|e| SaveError::Metadata { path: path, source: e })
+    }
+
+    pub fn save_metadata(&self, metadata: &WorldMetadata) -> Result<(), SaveError> {
+        let path = self.directory.join(METADATA_FILE);
+        let contents = serde_json::to_vec_pretty(metadata).map_err(|e| SaveError::Metadata { path: path.clone(), source: e })?;
+
+        write_atomic(&path, &contents)
+    }
+
+    // Every chunk stored in every region file, with block ids remapped onto the world palette.
+    // Blocks the palette doesn't know yet are added to it
+    pub fn load_chunks(&self, palette: &mut Vec<String>) -> Result<HashMap<chunk::ChunkPosition, chunk::Chunk>, SaveError> {
+        let region_directory = self.directory.join(REGION_DIRECTORY);
+        let mut chunks = HashMap::new();
+
+        let entries = match fs::read_dir(&region_directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(chunks),
+            Err(error) => return Err(SaveError::Io { path: region_directory, source: error })
+        };
+
+        for entry in entries {
+            let path = entry.map_err(|e| SaveError::Io { path: region_directory.clone(), source: e })?.path();
+
+            if path.extension().is_some_and(|extension| extension == "region") {
+                let region = read_region(&path, palette)?;
+
+                chunks.extend(remap_region(&path, region, palette)?);
+            }
+        }
+
+        return Ok(chunks);
+    }
+
+    // Write chunks into their region files, keeping whatever else those regions already hold.
+    // Block ids in the chunks refer to the world palette
+    pub fn save_chunks(&self, chunks: &[(chunk::ChunkPosition, chunk::Chunk)], palette: &[String]) -> Result<(), SaveError> {
+        let mut regions: HashMap<RegionPosition, Vec<usize>> = HashMap::new();
+
+        for (index, (position, _)) in chunks.iter().enumerate() {
+            regions.entry(region_of(*position)).or_default().push(index);
+        }
+
+        let region_directory = self.directory.join(REGION_DIRECTORY);
+        fs::create_dir_all(&region_directory).map_err(|e| SaveError::Io { path: region_directory.clone(), source: e })?;
+
+        for (region, entries) in regions {
+            let path = region_directory.join(format!("r.{}.{}.{}.region", region.0, region.1, region.2));
+
+            let mut region_palette = palette.to_vec();
+
+            let mut stored = if path.exists() {
+                let region = read_region(&path, palette)?;
+
+                remap_region(&path, region, &mut region_palette)?
+            } else {
+                HashMap::new()
+            };
+
+            for index in entries {
+                let (position, changed) = &chunks[index];
+
+                stored.insert(*position, changed.clone());
+            }
+
+            write_region(&path, &region_palette, &stored)?;
+        }
+
+        return Ok(());
+    }
+}
+
+fn check_version(path: &Path, version: u32) -> Result<(), SaveError> {
+    if (version > FORMAT_VERSION) {
+        return Err(SaveError::TooNew { path: path.to_path_buf(), version: version });
+    }
+
+    if (version == 0) {
+        return Err(SaveError::Corrupt { path: path.to_path_buf(), reason: "unknown format version 0".to_string() });
+    }
+
+    return Ok(());
+}
+
+// Version 2 only changed region files, the metadata layout is the same
+fn metadata_v1_to_v2(_metadata: &mut serde_json::Value) -> Result<(), String> {
+    return Ok(());
+}
+
+// Worlds saved before the day/night cycle carry on from morning
+fn metadata_v2_to_v3(metadata: &mut serde_json::Value) -> Result<(), String> {
+    let fields = metadata.as_object_mut().ok_or("metadata is not an object")?;
+
+    fields.entry("time_of_day").or_insert(serde_json::Value::from(daytime::MORNING));
+
+    return Ok(());
+}
+
+// Version 1 regions had no palette of their own, their ids index the palette in the metadata
+fn region_v1_to_v2(region: &mut Region, world_palette: &[String]) -> Result<(), String> {
+    region.palette = world_palette.to_vec();
+
+    return Ok(());
+}
+
+// Version 3 only changed the metadata, regions are the same
+fn region_v2_to_v3(_region: &mut Region, _world_palette: &[String]) -> Result<(), String> {
+    return Ok(());
+}
+
+fn region_of(position: chunk::ChunkPosition) -> RegionPosition {
+    (position.0.div_euclid(REGION_SIZE), position.1.div_euclid(REGION_SIZE), position.2.div_euclid(REGION_SIZE))
+}
+
+// Write to a temporary file first so a crash mid-save can't leave a half written file behind
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), SaveError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| SaveError::Io { path: parent.to_path_buf(), source: e })?;
+    }
+
+    let temporary = path.with_extension("tmp");
+
+    fs::write(&temporary, contents).map_err(|e| SaveError::Io { path: temporary.clone(), source: e })?;
+    fs::rename(&temporary, path).map_err(|e| SaveError::Io { path: path.to_path_buf(), source: e })
+}
+
+// A region file as read from disk, block ids index its own palette
+struct Region {
+    palette: Vec<String>,
+    chunks: HashMap<chunk::ChunkPosition, chunk::Chunk>
+}
+
+// Map the ids in a region onto a palette, registering any names it doesn't have yet
+fn remap_region(path: &Path, region: Region, palette: &mut Vec<String>) -> Result<HashMap<chunk::ChunkPosition, chunk::Chunk>, SaveError> {
+    let mapping: Vec<u16> = region.palette.iter().map(|name| {
+        match palette.iter().position(|entry| entry == name) {
+            Some(id) => id as u16,
+            None => {
+                palette.push(name.clone());
+                (palette.len() - 1) as u16
+            }
+        }
+    }).collect();
+
+    let mut chunks = HashMap::new();
+
+    for (position, stored) in region.chunks {
+        let mut blocks = Vec::with_capacity(chunk::CHUNK_VOLUME);
+
+        for block in stored.get_blocks() {
+            match mapping.get(*block as usize) {
+                Some(id) => blocks.push(*id),
+                None => return Err(SaveError::Corrupt { path: path.to_path_buf(), reason: format!("block id {} is not in the region palette", block) })
+            }
+        }
+
+        chunks.insert(position, chunk::Chunk::from_blocks(blocks).unwrap());
+    }
+
+    return Ok(chunks);
+}
+
+// Region layout, all little endian:
+//   magic "BGRN", format version (u32)
+//   palette size (u32), per block name: length (u16), UTF-8 name
+//   chunk count (u32), per chunk: position (3 × i32), compressed length (u32), zlib compressed block ids (CHUNK_VOLUME × u16)
+// Version 1 had no palette section
+fn write_region(path: &Path, palette: &[String], chunks: &HashMap<chunk::ChunkPosition, chunk::Chunk>) -> Result<(), SaveError> {
+    let io_error = |e| SaveError::Io { path: path.to_path_buf(), source: e };
+
+    let mut positions: Vec<&chunk::ChunkPosition> = chunks.keys().collect();
+    positions.sort();
+
+    let mut contents = Vec::new();
+    contents.extend_from_slice(REGION_MAGIC);
+    contents.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    contents.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+
+    for name in palette {
+        contents.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        contents.extend_from_slice(name.as_bytes());
+    }
+
+    contents.extend_from_slice(&(positions.len() as u32).to_le_bytes());
+
+    for position in positions {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+        for block in chunks[position].get_blocks() {
+            encoder.write_all(&block.to_le_bytes()).map_err(io_error)?;
+        }
+
+        let compressed = encoder.finish().map_err(io_error)?;
+
+        for coordinate in [position.0, position.1, position.2] {
+            contents.extend_from_slice(&coordinate.to_le_bytes());
+        }
+
+        contents.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        contents.extend_from_slice(&compressed);
+    }
+
+    write_atomic(path, &contents)
+}
+
+// Read a region and upgrade it to the current format. The world palette is what version 1 ids refer to
+fn read_region(path: &Path, world_palette: &[String]) -> Result<Region, SaveError> {
+    let contents = fs::read(path).map_err(|e| SaveError::Io { path: path.to_path_buf(), source: e })?;
+    let corrupt = |reason: &str| SaveError::Corrupt { path: path.to_path_buf(), reason: reason.to_string() };
+
+    let mut reader = RegionReader { contents: &contents, offset: 0 };
+
+    if reader.take(4) != Some(REGION_MAGIC.as_slice()) {
+        return Err(corrupt("not a region file"));
+    }
+
+    let version = reader.u32().ok_or_else(|| corrupt("truncated header"))?;
+
+    check_version(path, version)?;
+
+    let mut palette = Vec::new();
+
+    if (version >= 2) {
+        let size = reader.u32().ok_or_else(|| corrupt("truncated palette"))?;
+
+        for _ in 0..size {
+            let length = reader.u16().ok_or_else(|| corrupt("truncated palette"))? as usize;
+            let name = reader.take(length).ok_or_else(|| corrupt("truncated palette"))?;
+
+            palette.push(String::from_utf8(name.to_vec()).map_err(|_| corrupt("block name is not valid UTF-8"))?);
+        }
+    }
+
+    let count = reader.u32().ok_or_else(|| corrupt("truncated header"))?;
+    let mut chunks = HashMap::new();
+
+    for _ in 0..count {
+        let position = (
+            reader.i32().ok_or_else(|| corrupt("truncated chunk header"))?,
+            reader.i32().ok_or_else(|| corrupt("truncated chunk header"))?,
+            reader.i32().ok_or_else(|| corrupt("truncated chunk header"))?
+        );
+        let length = reader.u32().ok_or_else(|| corrupt("truncated chunk header"))? as usize;
+        let compressed = reader.take(length).ok_or_else(|| corrupt("truncated chunk data"))?;
+
+        let mut bytes = Vec::new();
+        ZlibDecoder::new(compressed).read_to_end(&mut bytes).map_err(|e| corrupt(&e.to_string()))?;
+
+        let blocks: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        let loaded = chunk::Chunk::from_blocks(blocks).ok_or_else(|| corrupt("chunk has the wrong number of blocks"))?;
+
+        chunks.insert(position, loaded);
+    }
+
+    let mut region = Region { palette: palette, chunks: chunks };
+
+    for (index, migration) in REGION_MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migration(&mut region, world_palette).map_err(|reason| corrupt(&format!("could not upgrade from version {}: {}", index + 1, reason)))?;
+    }
+
+    return Ok(region);
+}
+
+struct RegionReader<'a> {
+    contents: &'a [u8],
+    offset: usize
+}
+
+impl<'a> RegionReader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.contents.get(self.offset..self.offset.checked_add(length)?)?;
+        self.offset += length;
+
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.take(4).map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_directory(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("block_game_save_{}_{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&path);
+
+        return path;
+    }
+
+    fn test_metadata() -> WorldMetadata {
+        WorldMetadata {
+            format_version: FORMAT_VERSION,
+            seed: 1234,
+            spawn: [0.5, 3.0, 0.5],
+            player: PlayerState {
+                position: [10.25, 4.0, -3.5],
+                rotation: [-0.3, 1.2, 0.0],
+                mode: player::MovementMode::Spectator
+            },
+            time_of_day: 0.6,
+            palette: vec!["air".to_string(), "grass".to_string(), "dirt".to_string()]
+        }
+    }
+
+    fn test_palette() -> Vec<String> {
+        ["air", "grass", "dirt", "stone", "sand", "gravel", "log", "leaves", "water", "glass"].iter().map(|name| name.to_string()).collect()
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("saves").join(name)
+    }
+
+    fn copy_fixture(name: &str, destination: &Path) {
+        fs::create_dir_all(destination.join(REGION_DIRECTORY)).unwrap();
+
+        fs::copy(fixture(name).join(METADATA_FILE), destination.join(METADATA_FILE)).unwrap();
+
+        for entry in fs::read_dir(fixture(name).join(REGION_DIRECTORY)).unwrap() {
+            let path = entry.unwrap().path();
+
+            fs::copy(&path, destination.join(REGION_DIRECTORY).join(path.file_name().unwrap())).unwrap();
+        }
+    }
+
+    // The fixtures fill whole layers, so one column tells the layers apart
+    fn column(loaded_chunk: &chunk::Chunk) -> Vec<u16> {
+        (0..4).map(|y| loaded_chunk.get_block(5, y, 9)).collect()
+    }
+
+    fn test_chunk(fill: u16) -> chunk::Chunk {
+        let mut test_chunk = chunk::Chunk::new();
+
+        test_chunk.set_block(0, 0, 0, fill);
+        test_chunk.set_block(15, 15, 15, fill + 1);
+        test_chunk.set_block(3, 7, 11, fill + 2);
+
+        test_chunk
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let directory = scratch_directory("metadata");
+        let world_save = WorldSave::new(&directory);
+
+        assert!(!world_save.exists());
+
+        world_save.save_metadata(&test_metadata()).unwrap();
+
+        assert!(world_save.exists());
+        assert_eq!(world_save.load_metadata().unwrap(), test_metadata());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn chunks_round_trip_across_regions() {
+        let directory = scratch_directory("chunks");
+        let world_save = WorldSave::new(&directory);
+
+        // Negative positions and a far away chunk land in different region files
+        let chunks = vec![((0, 0, 0), test_chunk(1)), ((-1, -1, -1), test_chunk(4)), ((40, 0, -17), test_chunk(7))];
+        world_save.save_chunks(&chunks, &test_palette()).unwrap();
+
+        let loaded = world_save.load_chunks(&mut test_palette()).unwrap();
+
+        assert_eq!(loaded.len(), 3);
+
+        for (position, saved) in chunks.iter() {
+            assert_eq!(loaded[position].get_blocks(), saved.get_blocks());
+        }
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn saving_keeps_other_chunks_in_the_region() {
+        let directory = scratch_directory("merge");
+        let world_save = WorldSave::new(&directory);
+
+        world_save.save_chunks(&[((0, 0, 0), test_chunk(1)), ((1, 0, 0), test_chunk(2))], &test_palette()).unwrap();
+        world_save.save_chunks(&[((1, 0, 0), test_chunk(0))], &test_palette()).unwrap();
+
+        let loaded = world_save.load_chunks(&mut test_palette()).unwrap();
+
+        assert_eq!(loaded[&(0, 0, 0)].get_blocks(), test_chunk(1).get_blocks());
+        assert_eq!(loaded[&(1, 0, 0)].get_blocks(), test_chunk(0).get_blocks());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn truncated_region_is_reported() {
+        let directory = scratch_directory("truncated");
+        let world_save = WorldSave::new(&directory);
+
+        world_save.save_chunks(&[((0, 0, 0), test_chunk(1))], &test_palette()).unwrap();
+
+        let path = directory.join(REGION_DIRECTORY).join("r.0.0.0.region");
+        let contents = fs::read(&path).unwrap();
+        fs::write(&path, &contents[..contents.len() - 10]).unwrap();
+
+        assert!(matches!(world_save.load_chunks(&mut test_palette()), Err(SaveError::Corrupt { .. })));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn chunks_are_remapped_onto_the_world_palette() {
+        let directory = scratch_directory("remap");
+        let world_save = WorldSave::new(&directory);
+
+        world_save.save_chunks(&[((0, 0, 0), test_chunk(1))], &test_palette()).unwrap();
+
+        let mut palette = vec!["air".to_string(), "stone".to_string(), "dirt".to_string()];
+        let loaded = world_save.load_chunks(&mut palette).unwrap();
+
+        // Blocks it didn't know yet are appended, the rest map to their existing ids
+        assert_eq!(palette[..4], ["air", "stone", "dirt", "grass"]);
+        assert_eq!(loaded[&(0, 0, 0)].get_block(0, 0, 0), 3);
+        assert_eq!(loaded[&(0, 0, 0)].get_block(15, 15, 15), 2);
+        assert_eq!(loaded[&(0, 0, 0)].get_block(3, 7, 11), 1);
+        assert_eq!(loaded[&(0, 0, 0)].get_block(1, 1, 1), 0);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn version_1_metadata_is_upgraded() {
+        let metadata = WorldSave::new(&fixture("v1")).load_metadata().unwrap();
+
+        assert_eq!(metadata.format_version, FORMAT_VERSION);
+        assert_eq!(metadata.seed, 1234);
+        assert_eq!(metadata.player.mode, player::MovementMode::Walking);
+        assert_eq!(metadata.palette, ["air", "grass", "dirt"]);
+        assert_eq!(metadata.time_of_day, daytime::MORNING);
+    }
+
+    #[test]
+    fn version_1_regions_use_the_metadata_palette() {
+        let world_save = WorldSave::new(&fixture("v1"));
+
+        let mut palette = world_save.load_metadata().unwrap().palette;
+        let loaded = world_save.load_chunks(&mut palette).unwrap();
+
+        assert_eq!(palette, ["air", "grass", "dirt"]);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(column(&loaded[&(0, 0, 0)]), [2, 2, 1, 0]);
+        assert_eq!(column(&loaded[&(1, 0, 0)]), [2, 1, 0, 0]);
+        assert_eq!(column(&loaded[&(-1, 0, 0)]), [2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn version_1_regions_are_rewritten_in_the_current_format() {
+        let directory = scratch_directory("upgrade");
+        copy_fixture("v1", &directory);
+
+        let world_save = WorldSave::new(&directory);
+        let palette = world_save.load_metadata().unwrap().palette;
+
+        // Saving one chunk rewrites its whole region, the untouched neighbour must survive the upgrade
+        world_save.save_chunks(&[((0, 0, 0), test_chunk(0))], &palette).unwrap();
+
+        let contents = fs::read(directory.join(REGION_DIRECTORY).join("r.0.0.0.region")).unwrap();
+        assert_eq!(u32::from_le_bytes(contents[4..8].try_into().unwrap()), FORMAT_VERSION);
+
+        let loaded = world_save.load_chunks(&mut palette.clone()).unwrap();
+
+        assert_eq!(loaded[&(0, 0, 0)].get_blocks(), test_chunk(0).get_blocks());
+        assert_eq!(column(&loaded[&(1, 0, 0)]), [2, 1, 0, 0]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn version_2_metadata_starts_in_the_morning() {
+        let metadata = WorldSave::new(&fixture("v2")).load_metadata().unwrap();
+
+        assert_eq!(metadata.format_version, FORMAT_VERSION);
+        assert_eq!(metadata.time_of_day, daytime::MORNING);
+        assert_eq!(metadata.palette, ["air", "grass", "dirt"]);
+    }
+
+    #[test]
+    fn metadata_without_a_version_3_field_is_refused() {
+        let directory = scratch_directory("missing_time");
+        copy_fixture("v2", &directory);
+
+        // Claiming to be current skips the upgrade that would fill the time in
+        let path = directory.join(METADATA_FILE);
+        let mut value: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        value["format_version"] = serde_json::Value::from(FORMAT_VERSION);
+        fs::write(&path, serde_json::to_vec(&value).unwrap()).unwrap();
+
+        assert!(matches!(WorldSave::new(&directory).load_metadata(), Err(SaveError::Metadata { .. })));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn version_2_regions_are_remapped() {
+        let world_save = WorldSave::new(&fixture("v2"));
+
+        let mut palette = world_save.load_metadata().unwrap().palette;
+        let loaded = world_save.load_chunks(&mut palette).unwrap();
+
+        // The region palette is air, dirt, grass, stone
+        assert_eq!(palette, ["air", "grass", "dirt", "stone"]);
+        assert_eq!(column(&loaded[&(0, 0, 0)]), [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn newer_metadata_is_refused() {
+        let directory = scratch_directory("newer_metadata");
+        copy_fixture("v2", &directory);
+
+        let path = directory.join(METADATA_FILE);
+        let mut value: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        value["format_version"] = serde_json::Value::from(FORMAT_VERSION + 1);
+        fs::write(&path, serde_json::to_vec(&value).unwrap()).unwrap();
+
+        let error = WorldSave::new(&directory).load_metadata().unwrap_err();
+
+        assert!(matches!(error, SaveError::TooNew { version, .. } if version == FORMAT_VERSION + 1));
+        assert!(error.to_string().contains("newer version"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn newer_regions_are_refused() {
+        let directory = scratch_directory("newer_region");
+        copy_fixture("v2", &directory);
+
+        let path = directory.join(REGION_DIRECTORY).join("r.0.0.0.region");
+        let mut contents = fs::read(&path).unwrap();
+        contents[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&path, contents).unwrap();
+
+        let world_save = WorldSave::new(&directory);
+
+        assert!(matches!(world_save.load_chunks(&mut test_palette()), Err(SaveError::TooNew { .. })));
+
+        // Saving into it must not overwrite what a newer build wrote
+        assert!(matches!(world_save.save_chunks(&[((0, 0, 0), test_chunk(1))], &test_palette()), Err(SaveError::TooNew { .. })));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
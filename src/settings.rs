@@ -0,0 +1,273 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera;
+use crate::input;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub projection: camera::Projection,
+    pub orthographic_size: f32,
+    // Keeps depth precise out at long render distances. Needs OpenGL 4.5 or ARB_clip_control,
+    // and is ignored without it
+    pub reverse_z: bool
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            fov: 70.0,
+            near: 0.1,
+            far: 1024.0,
+            projection: camera::Projection::Perspective,
+            orthographic_size: 64.0,
+            reverse_z: false
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlsSettings {
+    // Degrees of camera rotation per unit of mouse movement
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    // Part of a stick or trigger's travel that is ignored, from 0.0 to 1.0
+    pub gamepad_deadzone: f32,
+    // Degrees per second the camera turns with the look stick pushed all the way
+    pub gamepad_look_speed: f32
+}
+
+impl Default for ControlsSettings {
+    fn default() -> ControlsSettings {
+        ControlsSettings {
+            mouse_sensitivity: 0.15,
+            invert_y: false,
+            gamepad_deadzone: 0.2,
+            gamepad_look_speed: 180.0
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    // Fullscreen window on the desktop, switches instantly and keeps the desktop resolution
+    Borderless,
+    // Takes over the monitor with its own video mode
+    Exclusive
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    // Inner size in logical pixels, remembered while windowed
+    pub width: u32,
+    pub height: u32,
+    // Outer top left corner in physical pixels, left to the OS when unset
+    pub position: Option<(i32, i32)>,
+    pub maximized: bool,
+    pub mode: WindowMode,
+    // Mode the fullscreen toggle switches to from windowed
+    pub fullscreen_mode: WindowMode
+}
+
+impl Default for WindowSettings {
+    fn default() -> WindowSettings {
+        WindowSettings {
+            width: 1280,
+            height: 720,
+            position: None,
+            maximized: false,
+            mode: WindowMode::Windowed,
+            fullscreen_mode: WindowMode::Borderless
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum CloudMode {
+    Off,
+    // A flat layer
+    Fast,
+    // Solid looking blocks of cloud
+    Fancy
+}
+
+// Full screen passes run on the finished frame, in the order they're listed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum PostEffect {
+    // Maps bright HDR colours down into the displayable range
+    Tonemap,
+    // Converts linear colour for the display. Without it the picture comes out too dark
+    Gamma,
+    // Smooths jagged edges, a cheaper alternative to MSAA
+    Fxaa,
+    // Tints the view blue while the camera is in a water block. Off by default as the world
+    // generator doesn't make any water yet
+    Underwater,
+    // Darkens the corners of the screen
+    Vignette
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    // Samples per pixel in the scene, 0 turns multisampling off. Lower counts are used if the driver
+    // can't do as many
+    pub msaa_samples: u16,
+    pub vsync: bool,
+    // Chunk columns kept loaded in every direction around the player
+    pub render_distance: i32,
+    // Frames per second to stop at, 0 for no limit
+    pub frame_cap: u32,
+    // Where fog begins and where it hides everything, as fractions of the render distance.
+    // Anything past 1 would let the edge of the loaded chunks show
+    pub fog_start: f32,
+    pub fog_end: f32,
+    // Vary the brightness of each block a little
+    pub color_jitter: bool,
+    pub clouds: CloudMode,
+    // Height of the bottom of the cloud layer
+    pub cloud_height: f32,
+    // Terrain casts shadows from the sun and moon
+    pub shadows: bool,
+    // Width and height of each shadow cascade's depth texture
+    pub shadow_resolution: u32,
+    pub post_effects: Vec<PostEffect>,
+    // Brightness of the scene going into tonemapping
+    pub exposure: f32
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> GraphicsSettings {
+        GraphicsSettings {
+            msaa_samples: 4,
+            vsync: true,
+            render_distance: 6,
+            frame_cap: 0,
+            fog_start: 0.5,
+            fog_end: 0.95,
+            color_jitter: true,
+            clouds: CloudMode::Fancy,
+            cloud_height: 48.0,
+            shadows: true,
+            shadow_resolution: 2048,
+            post_effects: vec![PostEffect::Tonemap, PostEffect::Vignette, PostEffect::Gamma],
+            exposure: 1.0
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourcePackSettings {
+    // Scanned for pack directories and .zip files
+    pub directory: String,
+    // Pack names from the highest priority to the lowest, overriding their manifests. Unlisted packs come after these
+    pub order: Vec<String>
+}
+
+impl Default for ResourcePackSettings {
+    fn default() -> ResourcePackSettings {
+        ResourcePackSettings {
+            directory: "resourcepacks".to_string(),
+            order: Vec::new()
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldSettings {
+    // Where the world is saved, and loaded from if it exists
+    pub directory: String,
+    // Seconds between autosaves, 0 only saves on exit
+    pub autosave_interval: f32,
+    // Real seconds in a day, 0 stops the clock
+    pub day_length: f32
+}
+
+impl Default for WorldSettings {
+    fn default() -> WorldSettings {
+        WorldSettings {
+            directory: "saves/world".to_string(),
+            autosave_interval: 60.0,
+            day_length: 1200.0
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub graphics: GraphicsSettings,
+    pub camera: CameraSettings,
+    pub controls: ControlsSettings,
+    pub bindings: input::Bindings,
+    pub resource_packs: ResourcePackSettings,
+    pub world: WorldSettings
+}
+
+impl Settings {
+    // Load the settings file, falling back to defaults for anything missing from it
+    pub fn load() -> Result<Settings, String> {
+        Settings::load_from(Path::new(SETTINGS_FILE))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(Path::new(SETTINGS_FILE))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Settings, String> {
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+
+        fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_settings_load_back_unchanged() {
+        let path = std::env::temp_dir().join(format!("block_game_settings_{}.json", std::process::id()));
+
+        let mut settings = Settings::default();
+        settings.window.width = 1600;
+        settings.window.position = Some((40, -20));
+        settings.window.mode = WindowMode::Borderless;
+        settings.camera.fov = 90.0;
+
+        settings.save_to(&path).unwrap();
+        let loaded = Settings::load_from(&path);
+        let _ = fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.window.width, 1600);
+        assert_eq!(loaded.window.position, Some((40, -20)));
+        assert_eq!(loaded.window.mode, WindowMode::Borderless);
+        assert_eq!(loaded.bindings, settings.bindings);
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&settings).unwrap());
+    }
+}
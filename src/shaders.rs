@@ -1,8 +1,34 @@
 use std::{collections::HashMap};
-use include_dir::{include_dir, Dir};
+use std::fmt;
 
-static SHADERS: Dir<'_> = include_dir!("shaders");
+use crate::assets;
 
+#[derive(Debug)]
+pub enum ShaderError {
+    NotFound(String),
+    InvalidUtf8(String),
+    // The driver rejected one stage, `log` is its GLSL compiler output
+    Compile { file: String, log: String },
+    Link { name: String, log: String },
+    NotLoaded(String)
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::NotFound(file) => write!(f, "shaders/{}: file not found", file),
+            ShaderError::InvalidUtf8(file) => write!(f, "shaders/{}: not valid UTF-8", file),
+            ShaderError::Compile { file, log } => write!(f, "shaders/{}: failed to compile\n{}", file, log.trim_end()),
+            ShaderError::Link { name, log } => write!(f, "shader '{}': failed to link\n{}", name, log.trim_end()),
+            ShaderError::NotLoaded(name) => write!(f, "shader '{}' was never loaded", name)
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// Shaders that encode colour for the display themselves, the framebuffer mustn't convert it to sRGB again
+const SRGB_OUTPUT_SHADERS: [&str; 1] = ["post"];
 
 struct Shader {
     vertex: String,
@@ -13,6 +39,24 @@ pub struct Shaders {
     loaded_shaders: HashMap<String, Shader>
 }
 
+// Contents of a shader source file
+fn read_source(assets: &assets::Assets, file_name: &str) -> Result<String, ShaderError> {
+    let source = assets.read(&format!("shaders/{}", file_name)).ok_or_else(|| ShaderError::NotFound(file_name.to_owned()))?;
+
+    match String::from_utf8(source.into_owned()) {
+        Ok(contents) => Ok(contents),
+        Err(_) => Err(ShaderError::InvalidUtf8(file_name.to_owned()))
+    }
+}
+
+fn vertex_file(name: &str) -> String {
+    return name.to_owned() + "-vert.glsl";
+}
+
+fn fragment_file(name: &str) -> String {
+    return name.to_owned() + "-frag.glsl";
+}
+
 impl Shaders {
     pub fn new() -> Shaders {
         Shaders {
@@ -20,36 +64,42 @@ impl Shaders {
         }
     }
 
-    pub fn load_shader(&mut self, file_name: &str) -> Result<bool, String> {
-        let fragment_name = file_name.to_owned() + "-frag.glsl";
-        let vertex_name = file_name.to_owned() + "-vert.glsl";
-
-        let fragment_source = SHADERS.get_file(fragment_name.to_owned()).unwrap(); //File::open("shaders/".to_owned() + &fragment_name).unwrap();
-        let vertex_source = SHADERS.get_file(vertex_name.to_owned()).unwrap();
-
-        let fragment_source_string = fragment_source.contents_utf8().unwrap();
-        let vertex_source_string = vertex_source.contents_utf8().unwrap();
+    pub fn load_shader(&mut self, assets: &assets::Assets, file_name: &str) -> Result<(), ShaderError> {
+        let fragment = read_source(assets, &fragment_file(file_name))?;
+        let vertex = read_source(assets, &vertex_file(file_name))?;
         
-        self.loaded_shaders.insert(file_name.to_owned(), Shader { vertex: vertex_source_string.to_string(), fragment: fragment_source_string.to_string() });
+        self.loaded_shaders.insert(file_name.to_owned(), Shader { vertex: vertex, fragment: fragment });
 
-        Ok(true)
+        Ok(())
     }
 
-    pub fn get_shader_program(&mut self, shader_name: &str, display: &glium::Display) -> Result<glium::Program, String> {
-        match self.loaded_shaders.get(shader_name) {
-            Some(shader) => {
-                Ok(
-                    glium::Program::from_source(
-                        display, 
-                        shader.vertex.as_str(), 
-                        shader.fragment.as_str(), 
-                        None
-                    ).unwrap()
-                )
+    pub fn get_shader_program(&mut self, shader_name: &str, display: &glium::Display) -> Result<glium::Program, ShaderError> {
+        let shader = self.loaded_shaders.get(shader_name).ok_or_else(|| ShaderError::NotLoaded(shader_name.to_owned()))?;
+
+        let program = glium::Program::new(display, glium::program::ProgramCreationInput::SourceCode {
+            vertex_shader: shader.vertex.as_str(),
+            tessellation_control_shader: None,
+            tessellation_evaluation_shader: None,
+            geometry_shader: None,
+            fragment_shader: shader.fragment.as_str(),
+            transform_feedback_varyings: None,
+            outputs_srgb: SRGB_OUTPUT_SHADERS.contains(&shader_name),
+            uses_point_size: false
+        });
+
+        program.map_err(|error| match error {
+            glium::ProgramCreationError::CompilationError(log, glium::program::ShaderType::Vertex) => {
+                ShaderError::Compile { file: vertex_file(shader_name), log: log }
+            },
+            glium::ProgramCreationError::CompilationError(log, _) => {
+                ShaderError::Compile { file: fragment_file(shader_name), log: log }
+            },
+            glium::ProgramCreationError::LinkingError(log) => {
+                ShaderError::Link { name: shader_name.to_owned(), log: log }
             },
-            None => {
-                Err("Shader not found".to_owned())
+            other => {
+                ShaderError::Link { name: shader_name.to_owned(), log: other.to_string() }
             }
-        }
+        })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,102 @@
+use crate::nalgebra;
+
+// Shadow maps rendered each frame. Each one covers a wider area around the camera than the last,
+// so nearby shadows get the most detail
+pub const CASCADES: usize = 3;
+
+// Radius of each cascade, as a fraction of the shadow distance
+const CASCADE_SCALE: [f32; CASCADES] = [0.1, 0.3, 1.0];
+
+// How far past a cascade towards the light terrain can still cast into it
+const CASTER_MARGIN: f32 = 128.0;
+
+pub struct Cascade {
+    // World space to the cascade's clip space
+    pub matrix: [[f32; 4]; 4],
+    // Distance from the camera the cascade covers
+    pub radius: f32
+}
+
+// Orthographic views from the light, centred on the camera. Centres are snapped to whole texels so
+// shadow edges don't crawl as the camera moves
+pub fn build_cascades(center: nalgebra::Point3<f32>, light_direction: nalgebra::Vector3<f32>, distance: f32, resolution: u32) -> Vec<Cascade> {
+    let light_direction = light_direction.normalize();
+
+    // Any up vector works as long as it isn't parallel to the light
+    let up = if light_direction.y.abs() < 0.99 { nalgebra::Vector3::y() } else { nalgebra::Vector3::z() };
+    let rotation = nalgebra::Matrix4::look_at_rh(&nalgebra::Point3::origin(), &nalgebra::Point3::from(-light_direction), &up);
+
+    let mut cascades = Vec::new();
+
+    for scale in CASCADE_SCALE {
+        let radius = distance * scale;
+        let texel = 2.0 * radius / resolution.max(1) as f32;
+
+        let light_center = rotation.transform_point(&center);
+        let snapped = nalgebra::Vector3::new(
+            (light_center.x / texel).floor() * texel,
+            (light_center.y / texel).floor() * texel,
+            light_center.z + radius + CASTER_MARGIN
+        );
+
+        let view = nalgebra::Matrix4::new_translation(&-snapped) * rotation;
+        let projection = nalgebra::Orthographic3::new(-radius, radius, -radius, radius, 0.0, 2.0 * radius + CASTER_MARGIN).to_homogeneous();
+
+        let matrix = projection * view;
+        let matrix_ref: &[[f32; 4]; 4] = matrix.as_ref();
+
+        cascades.push(Cascade {
+            matrix: *matrix_ref,
+            radius: radius
+        });
+    }
+
+    return cascades;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(cascade: &Cascade, point: nalgebra::Point3<f32>) -> nalgebra::Vector3<f32> {
+        let matrix = nalgebra::Matrix4::from(cascade.matrix);
+
+        matrix.transform_point(&point).coords
+    }
+
+    #[test]
+    fn camera_is_in_the_middle_of_every_cascade() {
+        let center = nalgebra::Point3::new(37.3, 20.0, -81.6);
+        let light = nalgebra::Vector3::new(0.4, 1.0, 0.3);
+        let cascades = build_cascades(center, light, 96.0, 1024);
+
+        assert_eq!(cascades.len(), CASCADES);
+
+        for cascade in cascades.iter() {
+            let clip = project(cascade, center);
+            let texel = 2.0 / 1024.0;
+
+            assert!(clip.x.abs() <= texel && clip.y.abs() <= texel);
+            assert!(clip.z.abs() < 1.0);
+        }
+
+        assert!(cascades[0].radius < cascades[1].radius && cascades[1].radius < cascades[2].radius);
+    }
+
+    #[test]
+    fn points_towards_the_light_are_nearer() {
+        let center = nalgebra::Point3::new(0.0, 10.0, 0.0);
+        let light = nalgebra::Vector3::new(0.0, 1.0, 0.0);
+        let cascades = build_cascades(center, light, 96.0, 1024);
+
+        for cascade in cascades.iter() {
+            let ground = project(cascade, center);
+            let hill = project(cascade, center + light * 5.0);
+            // Out past the cascade, but still close enough to cast into it
+            let peak = project(cascade, center + light * (cascade.radius + CASTER_MARGIN * 0.5));
+
+            assert!(hill.z < ground.z);
+            assert!(peak.z < hill.z && peak.z > -1.0);
+        }
+    }
+}
@@ -1,8 +1,49 @@
 use std::{collections::HashMap};
+use std::fmt;
 use image::ImageBuffer;
-use include_dir::{include_dir, Dir};
+use serde::Deserialize;
 
-static TEXTURES: Dir<'_> = include_dir!("textures");
+use crate::assets;
+use crate::blockbuilder;
+
+#[derive(Debug)]
+pub enum TextureError {
+    NotFound(String),
+    Decode { file: String, source: image::ImageError },
+    Upload { name: String, source: glium::texture::TextureCreationError },
+    NotLoaded(String),
+    // `textures/animations.json` is malformed or doesn't match its images
+    Animation(String)
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::NotFound(file) => write!(f, "textures/{}: file not found", file),
+            TextureError::Decode { file, source } => write!(f, "textures/{}: {}", file, source),
+            TextureError::Upload { name, source } => write!(f, "texture '{}': could not upload to the GPU: {}", name, source),
+            TextureError::NotLoaded(name) => write!(f, "texture '{}' was never loaded", name),
+            TextureError::Animation(message) => write!(f, "textures/{}: {}", ANIMATIONS_FILE, message)
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+// Which atlas tiles are animated, keyed by the image holding their frames
+pub const ANIMATIONS_FILE: &str = "animations.json";
+
+// Decode an embedded PNG into RGBA pixels
+fn decode_image(assets: &assets::Assets, file_name: &str) -> Result<ImageBuffer<image::Rgba<u8>, Vec<u8>>, TextureError> {
+    let image_name = file_name.to_owned() + ".png";
+
+    let image_source = assets.read(&format!("textures/{}", image_name)).ok_or_else(|| TextureError::NotFound(image_name.clone()))?;
+
+    match image::load_from_memory(&image_source) {
+        Ok(loaded_image) => Ok(loaded_image.to_rgba8()),
+        Err(error) => Err(TextureError::Decode { file: image_name, source: error })
+    }
+}
 
 struct Texture {
     rgba8: ImageBuffer<image::Rgba<u8>, Vec<u8>>,
@@ -20,14 +61,8 @@ impl Textures {
         }
     }
 
-    pub fn load_image(&mut self, file_name: &str) -> Result<bool, String> {
-        let image_name = file_name.to_owned() + ".png";
-
-        let image_source = TEXTURES.get_file(image_name.to_owned()).unwrap(); //File::open("shaders/".to_owned() + &fragment_name).unwrap();
-
-        let image_bytes = image_source.contents();
-
-        let loaded_image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    pub fn load_image(&mut self, assets: &assets::Assets, file_name: &str) -> Result<(), TextureError> {
+        let loaded_image = decode_image(assets, file_name)?;
         let dimensions = loaded_image.dimensions();
 
         self.loaded_textures.insert(file_name.to_owned(), Texture {
@@ -35,34 +70,282 @@ impl Textures {
             dimensions: dimensions
         });
 
-        Ok(true)
+        Ok(())
     }
 
-    pub fn icon_rgba8(&self, file_name: &str) -> Vec<u8> {
-        let image_name = file_name.to_owned() + ".png";
-
-        let image_source = TEXTURES.get_file(image_name.to_owned()).unwrap(); //File::open("shaders/".to_owned() + &fragment_name).unwrap();
-
-        let image_bytes = image_source.contents();
-
-        let loaded_image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    // Raw pixels of an image along with its width and height
+    pub fn icon_rgba8(&self, assets: &assets::Assets, file_name: &str) -> Result<(Vec<u8>, u32, u32), TextureError> {
+        let loaded_image = decode_image(assets, file_name)?;
+        let (width, height) = loaded_image.dimensions();
         
-        return loaded_image.into_raw();
+        return Ok((loaded_image.into_raw(), width, height));
     }
 
-    pub fn get_texture(&mut self, texture_name: &str, display: &glium::Display) -> Result<glium::texture::SrgbTexture2d, String> {
+    // Pixels of a loaded image, for images that are read rather than drawn
+    pub fn get_rgba8(&self, texture_name: &str) -> Result<&ImageBuffer<image::Rgba<u8>, Vec<u8>>, TextureError> {
+        match self.loaded_textures.get(texture_name) {
+            Some(texture) => Ok(&texture.rgba8),
+            None => Err(TextureError::NotLoaded(texture_name.to_owned()))
+        }
+    }
+
+    pub fn get_texture(&mut self, texture_name: &str, display: &glium::Display) -> Result<glium::texture::SrgbTexture2d, TextureError> {
         match self.loaded_textures.get(texture_name) {
             Some(texture) => {
-                let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&texture.rgba8.as_raw(), texture.dimensions);
-                let texture = glium::texture::SrgbTexture2d::new(display, image).unwrap();
-                
-                Ok(
-                    texture
-                )
+                let image = glium::texture::RawImage2d::from_raw_rgba_reversed(texture.rgba8.as_raw(), texture.dimensions);
+
+                glium::texture::SrgbTexture2d::new(display, image)
+                    .map_err(|error| TextureError::Upload { name: texture_name.to_owned(), source: error })
             },
             None => {
-                Err("Texture not found".to_owned())
+                Err(TextureError::NotLoaded(texture_name.to_owned()))
+            }
+        }
+    }
+}
+
+// One tile's animation as written in `textures/animations.json`
+#[derive(Deserialize)]
+pub struct AnimationDefinition {
+    // Atlas position of the tile in pixels, the same as in block files
+    pub tile: (u32, u32),
+    // Frames stacked top to bottom in the animation's image
+    pub frames: u32,
+    // Seconds each frame is shown for
+    pub frame_time: f32,
+    // Fade between frames instead of switching
+    #[serde(default)]
+    pub interpolate: bool
+}
+
+struct TileAnimation {
+    image_name: String,
+    tile: (u32, u32),
+    frames: Vec<image::RgbaImage>,
+    frame_time: f32,
+    interpolate: bool,
+    // Frame last written into the atlas
+    shown: Option<usize>
+}
+
+impl TileAnimation {
+    // Pixels for the current time, or None if the atlas already has them
+    fn update(&mut self, time: f32) -> Option<image::RgbaImage> {
+        let position = time / self.frame_time.max(0.001);
+        let frame = position.floor() as usize % self.frames.len();
+
+        if !self.interpolate {
+            if self.shown == Some(frame) {
+                return None;
+            }
+
+            self.shown = Some(frame);
+
+            return Some(self.frames[frame].clone());
+        }
+
+        let next = &self.frames[(frame + 1) % self.frames.len()];
+        let amount = position.fract();
+
+        self.shown = Some(frame);
+
+        let mut blended = self.frames[frame].clone();
+
+        for (pixel, next_pixel) in blended.pixels_mut().zip(next.pixels()) {
+            for channel in 0..4 {
+                pixel[channel] = (pixel[channel] as f32 + (next_pixel[channel] as f32 - pixel[channel] as f32) * amount).round() as u8;
             }
         }
+
+        return Some(blended);
+    }
+}
+
+// Atlas tiles that change over time. Frames come from their own images and are copied over the tile
+pub struct Animations {
+    animations: Vec<TileAnimation>,
+    time: f32
+}
+
+impl Animations {
+    pub fn new() -> Animations {
+        Animations {
+            animations: Vec::new(),
+            time: 0.0
+        }
+    }
+
+    // Read the animation list and every frame image. A pack without the list has no animations
+    pub fn load(assets: &assets::Assets) -> Result<Animations, TextureError> {
+        let mut loaded = Animations::new();
+
+        let contents = match assets.read(&format!("textures/{}", ANIMATIONS_FILE)) {
+            Some(contents) => contents,
+            None => return Ok(loaded)
+        };
+
+        let definitions: HashMap<String, AnimationDefinition> = serde_json::from_slice(&contents)
+            .map_err(|error| TextureError::Animation(error.to_string()))?;
+
+        for (image_name, definition) in definitions {
+            let image = decode_image(assets, &image_name)?;
+            let (width, _) = image.dimensions();
+
+            check_animation(&image_name, &definition, image.dimensions())?;
+
+            let frames = (0..definition.frames).map(|frame| {
+                image::imageops::crop_imm(&image, 0, frame * width, width, width).to_image()
+            }).collect();
+
+            loaded.animations.push(TileAnimation {
+                image_name: image_name,
+                tile: definition.tile,
+                frames: frames,
+                frame_time: definition.frame_time,
+                interpolate: definition.interpolate,
+                shown: None
+            });
+        }
+
+        return Ok(loaded);
+    }
+
+    // Whether an image holds frames for one of the animations
+    #[cfg_attr(not(feature = "hot-reload"), allow(dead_code))]
+    pub fn uses_image(&self, image_name: &str) -> bool {
+        return self.animations.iter().any(|animation| animation.image_name == image_name);
+    }
+
+    // Advance the clock, returning the tiles that need new pixels and their atlas positions
+    pub fn tick(&mut self, delta: f32) -> Vec<((u32, u32), image::RgbaImage)> {
+        self.time += delta;
+
+        let time = self.time;
+
+        return self.animations.iter_mut()
+            .filter_map(|animation| animation.update(time).map(|pixels| (animation.tile, pixels)))
+            .collect();
+    }
+}
+
+// Frames have to be whole atlas tiles, and the tile they replace has to be inside the atlas
+fn check_animation(image_name: &str, definition: &AnimationDefinition, (width, height): (u32, u32)) -> Result<(), TextureError> {
+    let tile_size = blockbuilder::TILE_SIZE as u32;
+    let atlas_size = blockbuilder::ATLAS_SIZE as u32;
+
+    if definition.frames == 0 || height != width.saturating_mul(definition.frames) {
+        return Err(TextureError::Animation(format!(
+            "'{}' should be {} square frames stacked vertically, but is {}x{}", image_name, definition.frames, width, height
+        )));
+    }
+
+    if width != tile_size {
+        return Err(TextureError::Animation(format!(
+            "'{}' has {}px frames, but atlas tiles are {}px", image_name, width, tile_size
+        )));
+    }
+
+    let (x, y) = definition.tile;
+
+    if x.saturating_add(tile_size) > atlas_size || y.saturating_add(tile_size) > atlas_size {
+        return Err(TextureError::Animation(format!(
+            "tile [{}, {}] for '{}' is outside the {}x{} atlas", x, y, image_name, atlas_size, atlas_size
+        )));
+    }
+
+    return Ok(());
+}
+
+// Copy pixels over part of an uploaded texture. `position` is in image pixels from the top left
+pub fn write_tile(texture: &glium::texture::SrgbTexture2d, position: (u32, u32), pixels: &image::RgbaImage) {
+    let (width, height) = pixels.dimensions();
+
+    // A resource pack can swap in a smaller atlas than the animations were checked against
+    if position.0.saturating_add(width) > texture.width() || position.1.saturating_add(height) > texture.height() {
+        return;
+    }
+
+    // Textures are uploaded upside down, so rows count from the bottom
+    let rect = glium::Rect {
+        left: position.0,
+        bottom: texture.height() - position.1 - height,
+        width: width,
+        height: height
+    };
+
+    texture.write(rect, glium::texture::RawImage2d::from_raw_rgba_reversed(pixels.as_raw(), (width, height)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_animation(interpolate: bool) -> Animations {
+        // Two frames of a single pixel, black then white
+        let frames = vec![image::RgbaImage::from_pixel(1, 1, image::Rgba([0, 0, 0, 255])), image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]))];
+
+        Animations {
+            animations: vec![TileAnimation {
+                image_name: "flash".to_string(),
+                tile: (32, 64),
+                frames: frames,
+                frame_time: 0.5,
+                interpolate: interpolate,
+                shown: None
+            }],
+            time: 0.0
+        }
+    }
+
+    #[test]
+    fn frames_are_only_written_when_they_change() {
+        let mut animations = test_animation(false);
+
+        let first = animations.tick(0.1);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].0, (32, 64));
+        assert_eq!(first[0].1.get_pixel(0, 0)[0], 0);
+
+        assert!(animations.tick(0.1).is_empty());
+        assert_eq!(animations.tick(0.4)[0].1.get_pixel(0, 0)[0], 255);
+
+        // Back to the start after the last frame
+        assert_eq!(animations.tick(0.5)[0].1.get_pixel(0, 0)[0], 0);
+    }
+
+    #[test]
+    fn interpolated_frames_fade_into_the_next() {
+        let mut animations = test_animation(true);
+
+        assert_eq!(animations.tick(0.25)[0].1.get_pixel(0, 0)[0], 128);
+        assert_eq!(animations.tick(0.25)[0].1.get_pixel(0, 0)[0], 255);
+    }
+
+    #[test]
+    fn shipped_animations_load() {
+        let animations = Animations::load(&assets::Assets::new(Vec::new())).unwrap();
+
+        assert!(animations.uses_image("lava"));
+        assert!(animations.uses_image("water"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn animations_must_fit_the_atlas() {
+        let definition = |tile: (u32, u32)| AnimationDefinition {
+            tile: tile,
+            frames: 2,
+            frame_time: 0.5,
+            interpolate: false
+        };
+
+        assert!(check_animation("flash", &definition((240, 240)), (16, 32)).is_ok());
+
+        // Past the right and bottom edges
+        assert!(matches!(check_animation("flash", &definition((256, 0)), (16, 32)), Err(TextureError::Animation(_))));
+        assert!(matches!(check_animation("flash", &definition((0, 250)), (16, 32)), Err(TextureError::Animation(_))));
+
+        // Frames that aren't one tile in size
+        assert!(matches!(check_animation("flash", &definition((0, 0)), (32, 64)), Err(TextureError::Animation(_))));
+        assert!(matches!(check_animation("flash", &definition((0, 0)), (16, 48)), Err(TextureError::Animation(_))));
+    }
+}
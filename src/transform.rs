@@ -1,13 +1,16 @@
+#[derive(Clone, Copy)]
 pub struct Transform {
     position: nalgebra::Point3<f32>,
-    rotation: nalgebra::Vector3<f32>
+    rotation: nalgebra::UnitQuaternion<f32>
 }
 
+// Not every accessor is needed by the game loop yet
+#[allow(dead_code)]
 impl Transform {
     pub fn new() -> Transform {
         Transform {
             position: nalgebra::Point3::new(0.0, 0.0, 0.0),
-            rotation: nalgebra::Vector3::new(0.0, 0.0, 0.0)
+            rotation: nalgebra::UnitQuaternion::identity()
         }
     }
 
@@ -19,46 +22,225 @@ impl Transform {
         self.position = position;
     }
 
-    pub fn get_rotation(&self) -> nalgebra::Vector3<f32> {
+    pub fn get_rotation(&self) -> nalgebra::UnitQuaternion<f32> {
         return self.rotation;
     }
 
-    pub fn set_rotation(&mut self, rotation: nalgebra::Vector3<f32>) {
+    pub fn set_rotation(&mut self, rotation: nalgebra::UnitQuaternion<f32>) {
         self.rotation = rotation;
     }
 
-    pub fn rotate_local(&mut self, angle: nalgebra::Vector3<f32>) {
-        self.rotation += angle;
+    // Euler angles as (pitch, yaw, roll), composed yaw first, then pitch, then roll (Y·X·Z)
+    pub fn get_euler_angles(&self) -> nalgebra::Vector3<f32> {
+        let m = self.rotation.to_rotation_matrix();
+
+        let pitch = (-m[(1, 2)]).clamp(-1.0, 1.0).asin();
+        let yaw = m[(0, 2)].atan2(m[(2, 2)]);
+        let roll = m[(1, 0)].atan2(m[(1, 1)]);
+
+        return nalgebra::Vector3::new(pitch, yaw, roll);
+    }
+
+    pub fn set_euler_angles(&mut self, angles: nalgebra::Vector3<f32>) {
+        self.rotation = euler_to_quaternion(angles);
+    }
+
+    pub fn forward(&self) -> nalgebra::Vector3<f32> {
+        return self.rotation * -nalgebra::Vector3::z();
+    }
+
+    pub fn right(&self) -> nalgebra::Vector3<f32> {
+        return self.rotation * nalgebra::Vector3::x();
+    }
+
+    pub fn up(&self) -> nalgebra::Vector3<f32> {
+        return self.rotation * nalgebra::Vector3::y();
+    }
+
+    pub fn translate_world(&mut self, direction: nalgebra::Vector3<f32>) {
+        self.position += direction;
     }
 
     pub fn translate_local(&mut self, direction: nalgebra::Vector3<f32>) {
-        let translation = nalgebra::Matrix4::new_translation(&direction);
-        
-        let rotation = self.rotation_matrix();
-        let inverse_rotation = rotation.try_inverse().unwrap();
+        self.position += self.rotation * direction;
+    }
 
-        self.position = rotation.transform_point(&(translation * inverse_rotation).transform_point(&self.position));
+    // Rotate about the world axes, e.g. yaw around the global up vector
+    pub fn rotate_world(&mut self, rotation: nalgebra::UnitQuaternion<f32>) {
+        self.rotation = rotation * self.rotation;
+    }
+
+    // Rotate about the transform's own axes, e.g. pitch around its right vector
+    pub fn rotate_local(&mut self, rotation: nalgebra::UnitQuaternion<f32>) {
+        self.rotation *= rotation;
+    }
+
+    // Point the forward (-Z) axis at a world position
+    pub fn look_at(&mut self, target: nalgebra::Point3<f32>, up: nalgebra::Vector3<f32>) {
+        let direction = target - self.position;
+
+        if direction.norm_squared() <= f32::EPSILON {
+            return;
+        }
+
+        self.rotation = nalgebra::UnitQuaternion::face_towards(&-direction, &up);
+    }
+
+    // Blend towards another transform, lerping position and slerping rotation
+    pub fn interpolate(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            position: self.position + (other.position - self.position) * t,
+            rotation: self.rotation.slerp(&other.rotation, t)
+        }
     }
 
     pub fn rotation_matrix(&self) -> nalgebra::Matrix4<f32> {
-        nalgebra::Matrix4::new_rotation(nalgebra::Vector3::new(0.0, self.rotation.y, 0.0)) *
-        nalgebra::Matrix4::new_rotation(nalgebra::Vector3::new(self.rotation.x, 0.0, 0.0)) *
-        nalgebra::Matrix4::new_rotation(nalgebra::Vector3::new(0.0, 0.0, self.rotation.z))
+        self.rotation.to_homogeneous()
     }
 
     pub fn get_matrix(&self) -> [[f32; 4]; 4] {
-        let mut mat4 = nalgebra::Matrix4::new_translation(&self.position.coords);
-        //mat4 *= nalgebra::Vector3::new(1.0, 1.0, 1.0); // Scaling
-        mat4 *= self.rotation_matrix();
-        mat4 *= nalgebra::Rotation::face_towards(
-            &nalgebra::Vector3::new(0.0, 0.0, 1.0), 
-            &nalgebra::Vector3::new(0.0, 1.0, 0.0)
-        ).to_homogeneous();
-        
-        let mat4_result = mat4.try_inverse().unwrap();
+        let isometry = nalgebra::Isometry3::from_parts(
+            nalgebra::Translation3::from(self.position.coords),
+            self.rotation
+        );
+
+        let mat4_result = isometry.inverse().to_homogeneous();
 
         let view_projection_ref: &[[f32; 4]; 4] = mat4_result.as_ref();
-        
+
         return *view_projection_ref;
     }
-}
\ No newline at end of file
+}
+
+fn euler_to_quaternion(angles: nalgebra::Vector3<f32>) -> nalgebra::UnitQuaternion<f32> {
+    nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::y_axis(), angles.y) *
+    nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::x_axis(), angles.x) *
+    nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::z_axis(), angles.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    // The matrix the Euler-based transform used to build for the camera
+    fn euler_matrix(angles: nalgebra::Vector3<f32>) -> nalgebra::Matrix4<f32> {
+        nalgebra::Matrix4::new_rotation(nalgebra::Vector3::new(0.0, angles.y, 0.0)) *
+        nalgebra::Matrix4::new_rotation(nalgebra::Vector3::new(angles.x, 0.0, 0.0)) *
+        nalgebra::Matrix4::new_rotation(nalgebra::Vector3::new(0.0, 0.0, angles.z))
+    }
+
+    fn assert_vector_eq(a: nalgebra::Vector3<f32>, b: nalgebra::Vector3<f32>) {
+        assert!((a - b).norm() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn euler_angles_compose_yaw_pitch_roll() {
+        let angles = nalgebra::Vector3::new(-0.7, 2.1, 0.3);
+        let mut transform = Transform::new();
+        transform.set_euler_angles(angles);
+
+        let expected = euler_matrix(angles);
+        let actual = transform.rotation_matrix();
+
+        assert!((expected - actual).norm() < EPSILON);
+    }
+
+    #[test]
+    fn euler_angles_round_trip() {
+        let angles = nalgebra::Vector3::new(1.2, -2.5, 0.4);
+        let mut transform = Transform::new();
+        transform.set_euler_angles(angles);
+
+        assert_vector_eq(transform.get_euler_angles(), angles);
+    }
+
+    #[test]
+    fn translate_local_matches_matrix_inverse_path() {
+        let angles = nalgebra::Vector3::new(-1.5, 0.8, 0.0);
+        let start = nalgebra::Point3::new(1.0, 2.0, 3.0);
+        let direction = nalgebra::Vector3::new(0.0, 0.0, -0.4);
+
+        let mut transform = Transform::new();
+        transform.set_position(start);
+        transform.set_euler_angles(angles);
+        transform.translate_local(direction);
+
+        let rotation = euler_matrix(angles);
+        let translation = nalgebra::Matrix4::new_translation(&direction);
+        let expected = rotation.transform_point(&(translation * rotation.try_inverse().unwrap()).transform_point(&start));
+
+        assert_vector_eq(transform.get_position().coords, expected.coords);
+    }
+
+    #[test]
+    fn translate_world_ignores_rotation() {
+        let mut transform = Transform::new();
+        transform.set_euler_angles(nalgebra::Vector3::new(0.3, 1.0, 0.0));
+        transform.translate_world(nalgebra::Vector3::new(0.0, 1.0, 0.0));
+
+        assert_vector_eq(transform.get_position().coords, nalgebra::Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn axes_follow_yaw() {
+        let mut transform = Transform::new();
+        assert_vector_eq(transform.forward(), nalgebra::Vector3::new(0.0, 0.0, -1.0));
+
+        transform.set_euler_angles(nalgebra::Vector3::new(0.0, std::f32::consts::FRAC_PI_2, 0.0));
+
+        assert_vector_eq(transform.forward(), nalgebra::Vector3::new(-1.0, 0.0, 0.0));
+        assert_vector_eq(transform.right(), nalgebra::Vector3::new(0.0, 0.0, -1.0));
+        assert_vector_eq(transform.up(), nalgebra::Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn rotate_world_and_local_apply_in_different_frames() {
+        let yaw = nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::y_axis(), 0.9);
+        let pitch = nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::x_axis(), -0.4);
+
+        let mut transform = Transform::new();
+        transform.rotate_local(pitch);
+        transform.rotate_world(yaw);
+
+        assert_vector_eq(transform.get_euler_angles(), nalgebra::Vector3::new(-0.4, 0.9, 0.0));
+    }
+
+    #[test]
+    fn look_at_points_forward_at_target() {
+        let mut transform = Transform::new();
+        transform.set_position(nalgebra::Point3::new(0.0, 5.0, 0.0));
+        transform.look_at(nalgebra::Point3::new(3.0, 5.0, 4.0), nalgebra::Vector3::y());
+
+        assert_vector_eq(transform.forward(), nalgebra::Vector3::new(0.6, 0.0, 0.8));
+        assert_vector_eq(transform.up(), nalgebra::Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn interpolate_blends_position_and_rotation() {
+        let mut from = Transform::new();
+        from.set_position(nalgebra::Point3::new(0.0, 0.0, 0.0));
+
+        let mut to = Transform::new();
+        to.set_position(nalgebra::Point3::new(2.0, 4.0, -6.0));
+        to.set_euler_angles(nalgebra::Vector3::new(0.0, 1.0, 0.0));
+
+        let halfway = from.interpolate(&to, 0.5);
+
+        assert_vector_eq(halfway.get_position().coords, nalgebra::Vector3::new(1.0, 2.0, -3.0));
+        assert_vector_eq(halfway.get_euler_angles(), nalgebra::Vector3::new(0.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn view_matrix_inverts_model_matrix() {
+        let mut transform = Transform::new();
+        transform.set_position(nalgebra::Point3::new(4.0, -2.0, 7.0));
+        transform.set_euler_angles(nalgebra::Vector3::new(0.2, -1.1, 0.05));
+
+        let view = nalgebra::Matrix4::from(transform.get_matrix());
+        let model = nalgebra::Matrix4::new_translation(&transform.get_position().coords) * transform.rotation_matrix();
+
+        assert!((view * model - nalgebra::Matrix4::identity()).norm() < EPSILON);
+    }
+}
@@ -0,0 +1,1075 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use noise::{NoiseFn, Perlin};
+
+use crate::biome;
+use crate::blockbuilder;
+use crate::chunk;
+use crate::cube;
+use crate::meshbuilder;
+use crate::models;
+
+// Vertical extent of the generated terrain, in chunks
+pub const MIN_CHUNK_Y: i32 = -1;
+pub const MAX_CHUNK_Y: i32 = 0;
+
+const BEDROCK_DEPTH: i32 = -15;
+
+// How much darker colour jitter can make a block
+const JITTER_STRENGTH: f32 = 0.08;
+
+// Biome tints are averaged over this many columns either side of a corner, so they blend across borders
+const TINT_BLEND_RADIUS: i32 = 2;
+
+const NEIGHBOURS: [(i32, i32, i32); 6] = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+
+// How a block affects the light around it, and whether things can pass through it
+#[derive(Clone, Copy)]
+struct BlockProperties {
+    opaque: bool,
+    emission: u8,
+    solid: bool
+}
+
+// Blocks without a definition block all light and movement
+const UNKNOWN_BLOCK: BlockProperties = BlockProperties { opaque: true, emission: 0, solid: true };
+
+#[derive(Clone, Copy, PartialEq)]
+enum LightChannel {
+    Sky,
+    Block
+}
+
+type BlockPosition = (i32, i32, i32);
+
+// The faces of one chunk, split by how they are drawn
+pub struct ChunkMesh {
+    pub opaque: meshbuilder::MeshBuilder,
+    // Faces of transparent blocks, blended over the opaque ones
+    pub translucent: meshbuilder::MeshBuilder
+}
+
+pub struct World {
+    seed: u32,
+    perlin: Perlin,
+    palette: Vec<String>,
+    chunks: HashMap<chunk::ChunkPosition, chunk::Chunk>,
+    block_properties: HashMap<String, BlockProperties>,
+    // `block_properties` looked up for every palette id
+    id_properties: Vec<BlockProperties>,
+    // Chunk columns that were added since lighting last ran
+    unlit_columns: HashSet<(i32, i32)>,
+    // Darken each block slightly by its position, so large areas of one block don't look flat
+    color_jitter: bool,
+    climate: biome::Climate,
+    // Tints for biome coloured faces, which stay white without one
+    colormap: Option<biome::Colormap>
+}
+
+impl World {
+    pub fn new(seed: u32) -> World {
+        let mut new_world = World {
+            seed: seed,
+            perlin: Perlin::new(seed),
+            palette: vec!["air".to_string()],
+            chunks: HashMap::new(),
+            block_properties: HashMap::new(),
+            id_properties: Vec::new(),
+            unlit_columns: HashSet::new(),
+            color_jitter: false,
+            climate: biome::Climate::new(seed),
+            colormap: None
+        };
+
+        new_world.refresh_block_properties();
+
+        return new_world;
+    }
+
+    pub fn get_seed(&self) -> u32 {
+        return self.seed;
+    }
+
+    pub fn get_palette(&self) -> &[String] {
+        return &self.palette;
+    }
+
+    // Use the ids from a saved world, chunks loaded from it refer to them
+    pub fn set_palette(&mut self, palette: Vec<String>) {
+        self.palette = palette;
+        self.refresh_block_properties();
+    }
+
+    // Only affects chunks meshed from now on
+    pub fn set_color_jitter(&mut self, color_jitter: bool) {
+        self.color_jitter = color_jitter;
+    }
+
+    // Only affects chunks meshed from now on
+    pub fn set_colormap(&mut self, colormap: biome::Colormap) {
+        self.colormap = Some(colormap);
+    }
+
+    // Take transparency, light emission and collision from the block definitions, then light everything again
+    pub fn set_block_properties(&mut self, blocks: &HashMap<String, blockbuilder::Block>) {
+        self.block_properties = blocks.iter()
+            .map(|(name, block)| (name.clone(), BlockProperties { opaque: !block.transparent, emission: block.light, solid: block.solid }))
+            .collect();
+
+        self.refresh_block_properties();
+        self.relight_all();
+    }
+
+    fn refresh_block_properties(&mut self) {
+        self.id_properties = self.palette.iter().enumerate().map(|(id, name)| {
+            if (id as u16 == chunk::AIR) {
+                BlockProperties { opaque: false, emission: 0, solid: false }
+            } else {
+                self.block_properties.get(name).copied().unwrap_or(UNKNOWN_BLOCK)
+            }
+        }).collect();
+    }
+
+    fn properties(&self, id: u16) -> BlockProperties {
+        return self.id_properties.get(id as usize).copied().unwrap_or(UNKNOWN_BLOCK);
+    }
+
+    // Add a chunk read from disk, it replaces any generated one
+    pub fn insert_chunk(&mut self, position: chunk::ChunkPosition, new_chunk: chunk::Chunk) {
+        self.chunks.insert(position, new_chunk);
+
+        let (cx, cy, cz) = position;
+        self.unlit_columns.insert((cx, cz));
+
+        for (ox, oy, oz) in NEIGHBOURS {
+            self.mark_for_mesh((cx + ox, cy + oy, cz + oz));
+        }
+    }
+
+    // Look up the numeric id for a block name, registering it in the palette if needed
+    pub fn block_id(&mut self, name: &str) -> u16 {
+        match self.palette.iter().position(|entry| entry == name) {
+            Some(id) => id as u16,
+            None => {
+                self.palette.push(name.to_string());
+                self.refresh_block_properties();
+                (self.palette.len() - 1) as u16
+            }
+        }
+    }
+
+    pub fn block_name(&self, id: u16) -> Option<&str> {
+        self.palette.get(id as usize).map(|name| name.as_str())
+    }
+
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> u16 {
+        let (chunk_position, (lx, ly, lz)) = chunk::to_chunk_coordinates(x, y, z);
+
+        match self.chunks.get(&chunk_position) {
+            Some(chunk) => chunk.get_block(lx, ly, lz),
+            None => chunk::AIR
+        }
+    }
+
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: u16) {
+        let (chunk_position, (lx, ly, lz)) = chunk::to_chunk_coordinates(x, y, z);
+
+        let created = !self.chunks.contains_key(&chunk_position);
+        let changed_chunk = self.chunks.entry(chunk_position).or_insert_with(chunk::Chunk::new);
+
+        changed_chunk.set_block(lx, ly, lz, block);
+        changed_chunk.needs_save = true;
+
+        // Faces on the border of the neighbouring chunks may have been covered or exposed, and the
+        // corners of any face touching the block are shaded by it, which reaches into diagonal chunks too
+        for ox in -1..=1 {
+            for oy in -1..=1 {
+                for oz in -1..=1 {
+                    self.mark_for_mesh(chunk::to_chunk_coordinates(x + ox, y + oy, z + oz).0);
+                }
+            }
+        }
+
+        let (cx, _, cz) = chunk_position;
+
+        // A new chunk has no light at all yet, so its whole column is lit from scratch
+        if created {
+            self.unlit_columns.insert((cx, cz));
+        } else {
+            self.update_light_at(x, y, z);
+        }
+    }
+
+    // Stops movement, unlike air and blocks like water
+    pub fn is_solid(&self, x: i32, y: i32, z: i32) -> bool {
+        return self.properties(self.get_block(x, y, z)).solid;
+    }
+
+    // Hides the faces behind it, unlike air and blocks marked transparent
+    pub fn is_opaque(&self, x: i32, y: i32, z: i32) -> bool {
+        return self.properties(self.get_block(x, y, z)).opaque;
+    }
+
+    fn mark_for_mesh(&mut self, position: chunk::ChunkPosition) {
+        if let Some(chunk) = self.chunks.get_mut(&position) {
+            chunk.needs_mesh = true;
+        }
+    }
+
+    // Remesh everything, e.g. after the block definitions changed
+    #[cfg_attr(not(feature = "hot-reload"), allow(dead_code))]
+    pub fn mark_all_for_mesh(&mut self) {
+        for chunk in self.chunks.values_mut() {
+            chunk.needs_mesh = true;
+        }
+    }
+
+    // Height of the terrain surface at a column, used by the generator and for spawning
+    pub fn surface_height(&self, x: i32, z: i32) -> i32 {
+        let noise = self.perlin.get([x as f64 * 0.025, z as f64 * 0.025]);
+
+        return f64::floor(noise * 2.0 - 1.0) as i32;
+    }
+
+    fn generate_chunk(&mut self, position: chunk::ChunkPosition) {
+        let grass = self.block_id("grass");
+        let dirt = self.block_id("dirt");
+
+        let mut new_chunk = chunk::Chunk::new();
+        let (cx, cy, cz) = position;
+
+        for lx in 0..chunk::CHUNK_SIZE {
+            for lz in 0..chunk::CHUNK_SIZE {
+                let x = cx * chunk::CHUNK_SIZE + lx;
+                let z = cz * chunk::CHUNK_SIZE + lz;
+                let height = self.surface_height(x, z);
+
+                for ly in 0..chunk::CHUNK_SIZE {
+                    let y = cy * chunk::CHUNK_SIZE + ly;
+
+                    if (y == height) {
+                        new_chunk.set_block(lx, ly, lz, grass);
+                    } else if (y < height && y > BEDROCK_DEPTH) {
+                        // Fill in the dirt below the grass
+                        new_chunk.set_block(lx, ly, lz, dirt);
+                    }
+                }
+            }
+        }
+
+        self.chunks.insert(position, new_chunk);
+        self.unlit_columns.insert((cx, cz));
+
+        // Neighbours meshed before this chunk existed have exposed faces along the shared border
+        for (ox, oy, oz) in NEIGHBOURS {
+            self.mark_for_mesh((cx + ox, cy + oy, cz + oz));
+        }
+    }
+
+    // Generate missing chunk columns around a block position, nearest first, up to a budget
+    pub fn generate_around(&mut self, x: i32, z: i32, radius: i32, budget: usize) -> usize {
+        let (center_x, _, center_z) = chunk::to_chunk_coordinates(x, 0, z).0;
+        let mut missing: Vec<(i32, i32)> = Vec::new();
+
+        // A column can be partly there already, when some of its chunks were loaded from a save
+        for cx in (center_x - radius)..=(center_x + radius) {
+            for cz in (center_z - radius)..=(center_z + radius) {
+                if (MIN_CHUNK_Y..=MAX_CHUNK_Y).any(|cy| !self.chunks.contains_key(&(cx, cy, cz))) {
+                    missing.push((cx, cz));
+                }
+            }
+        }
+
+        missing.sort_by_key(|(cx, cz)| (cx - center_x).pow(2) + (cz - center_z).pow(2));
+
+        let generated = missing.len().min(budget);
+
+        for (cx, cz) in missing.into_iter().take(budget) {
+            for cy in MIN_CHUNK_Y..=MAX_CHUNK_Y {
+                if !self.chunks.contains_key(&(cx, cy, cz)) {
+                    self.generate_chunk((cx, cy, cz));
+                }
+            }
+        }
+
+        return generated;
+    }
+
+    #[allow(dead_code)]
+    pub fn get_sky_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        return self.get_light(LightChannel::Sky, (x, y, z));
+    }
+
+    #[allow(dead_code)]
+    pub fn get_block_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        return self.get_light(LightChannel::Block, (x, y, z));
+    }
+
+    // Chunks in columns still waiting to be lit count as not loaded
+    fn get_lit_chunk(&self, position: chunk::ChunkPosition) -> Option<&chunk::Chunk> {
+        if (!self.unlit_columns.is_empty() && self.unlit_columns.contains(&(position.0, position.2))) {
+            return None;
+        }
+
+        return self.chunks.get(&position);
+    }
+
+    // Anything above the generated terrain that isn't loaded is open sky
+    fn get_light(&self, channel: LightChannel, position: BlockPosition) -> u8 {
+        let (chunk_position, (lx, ly, lz)) = chunk::to_chunk_coordinates(position.0, position.1, position.2);
+
+        match (self.get_lit_chunk(chunk_position), channel) {
+            (Some(chunk), LightChannel::Sky) => chunk.get_sky_light(lx, ly, lz),
+            (Some(chunk), LightChannel::Block) => chunk.get_block_light(lx, ly, lz),
+            (None, LightChannel::Sky) if chunk_position.1 > MAX_CHUNK_Y => chunk::MAX_LIGHT,
+            (None, _) => 0
+        }
+    }
+
+    // Sky and block light together, for when both are needed
+    fn get_light_levels(&self, position: BlockPosition) -> (u8, u8) {
+        let (chunk_position, (lx, ly, lz)) = chunk::to_chunk_coordinates(position.0, position.1, position.2);
+
+        match self.get_lit_chunk(chunk_position) {
+            Some(chunk) => (chunk.get_sky_light(lx, ly, lz), chunk.get_block_light(lx, ly, lz)),
+            None => (self.get_light(LightChannel::Sky, position), 0)
+        }
+    }
+
+    // Returns false when the chunk isn't loaded or lit yet
+    fn set_light(&mut self, channel: LightChannel, position: BlockPosition, level: u8) -> bool {
+        let (chunk_position, (lx, ly, lz)) = chunk::to_chunk_coordinates(position.0, position.1, position.2);
+
+        if (!self.unlit_columns.is_empty() && self.unlit_columns.contains(&(chunk_position.0, chunk_position.2))) {
+            return false;
+        }
+
+        match self.chunks.get_mut(&chunk_position) {
+            Some(chunk) => {
+                match channel {
+                    LightChannel::Sky => chunk.set_sky_light(lx, ly, lz, level),
+                    LightChannel::Block => chunk.set_block_light(lx, ly, lz, level)
+                }
+
+                true
+            },
+            None => false
+        }
+    }
+
+    // Light the chunk columns added since the last call. Light doesn't spread into the
+    // columns still in the queue, they pull it in once it's their turn
+    pub fn update_light(&mut self) {
+        while let Some(&(cx, cz)) = self.unlit_columns.iter().next() {
+            self.unlit_columns.remove(&(cx, cz));
+            self.light_column(cx, cz);
+        }
+    }
+
+    fn relight_all(&mut self) {
+        for (position, chunk) in self.chunks.iter_mut() {
+            chunk.clear_light();
+            self.unlit_columns.insert((position.0, position.2));
+        }
+    }
+
+    // Compute the light of a whole chunk column from scratch: sunlight from above, light
+    // from emitting blocks and whatever the neighbouring columns already hold
+    fn light_column(&mut self, cx: i32, cz: i32) {
+        let layers: Vec<i32> = self.chunks.keys().filter(|(x, _, z)| *x == cx && *z == cz).map(|position| position.1).collect();
+
+        let top = match layers.iter().max() {
+            Some(top) => *top,
+            None => return
+        };
+
+        let size = chunk::CHUNK_SIZE;
+        let (x0, z0) = (cx * size, cz * size);
+
+        let mut changed = HashSet::new();
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+
+        for cy in layers.iter() {
+            let position = (cx, *cy, cz);
+            let current_chunk = self.chunks.get_mut(&position).unwrap();
+
+            current_chunk.clear_light();
+            changed.insert(position);
+
+            for lx in 0..size {
+                for ly in 0..size {
+                    for lz in 0..size {
+                        let emission = self.id_properties.get(current_chunk.get_block(lx, ly, lz) as usize).map_or(0, |properties| properties.emission);
+
+                        if emission > 0 {
+                            current_chunk.set_block_light(lx, ly, lz, emission);
+                            block_queue.push_back((x0 + lx, cy * size + ly, z0 + lz));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Direct sunlight goes straight down each column of blocks until something stops it.
+        // `sunlit_from` is the lowest directly lit y of each column
+        let top_y = top * size + size - 1;
+        let bottom_y = layers.iter().min().unwrap() * size;
+        let mut sunlit_from = vec![top_y + 1; (size * size) as usize];
+
+        for lx in 0..size {
+            for lz in 0..size {
+                let (x, z) = (x0 + lx, z0 + lz);
+                let mut y = top_y;
+
+                while y >= bottom_y && !self.properties(self.get_block(x, y, z)).opaque && self.set_light(LightChannel::Sky, (x, y, z), chunk::MAX_LIGHT) {
+                    y -= 1;
+                }
+
+                sunlit_from[(lx + lz * size) as usize] = y + 1;
+            }
+        }
+
+        // Only sunlight next to something darker needs to spread sideways
+        for lx in 0..size {
+            for lz in 0..size {
+                let (x, z) = (x0 + lx, z0 + lz);
+
+                for y in sunlit_from[(lx + lz * size) as usize]..=top_y {
+                    let spreads = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|(ox, oz)| {
+                        let (nx, nz) = (lx + ox, lz + oz);
+
+                        if (0..size).contains(&nx) && (0..size).contains(&nz) {
+                            sunlit_from[(nx + nz * size) as usize] > y
+                        } else {
+                            self.get_light(LightChannel::Sky, (x + ox, y, z + oz)) < chunk::MAX_LIGHT - 1
+                        }
+                    });
+
+                    if spreads {
+                        sky_queue.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+
+        // Light already in the neighbouring columns flows back in across the border
+        for cy in layers.iter() {
+            for y in (cy * size)..(cy * size + size) {
+                for i in 0..size {
+                    let borders = [
+                        ((x0 - 1, y, z0 + i), (x0, y, z0 + i)),
+                        ((x0 + size, y, z0 + i), (x0 + size - 1, y, z0 + i)),
+                        ((x0 + i, y, z0 - 1), (x0 + i, y, z0)),
+                        ((x0 + i, y, z0 + size), (x0 + i, y, z0 + size - 1))
+                    ];
+
+                    for (outside, inside) in borders {
+                        if self.get_light(LightChannel::Sky, outside) > self.get_light(LightChannel::Sky, inside) + 1 {
+                            sky_queue.push_back(outside);
+                        }
+
+                        if self.get_light(LightChannel::Block, outside) > self.get_light(LightChannel::Block, inside) + 1 {
+                            block_queue.push_back(outside);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.spread_light(LightChannel::Sky, &mut sky_queue, &mut changed);
+        self.spread_light(LightChannel::Block, &mut block_queue, &mut changed);
+
+        self.mark_light_changed(changed);
+    }
+
+    // Fix up the light around a block that was just placed or broken
+    fn update_light_at(&mut self, x: i32, y: i32, z: i32) {
+        let properties = self.properties(self.get_block(x, y, z));
+        let mut changed = HashSet::new();
+
+        for channel in [LightChannel::Sky, LightChannel::Block] {
+            let mut removal = VecDeque::new();
+            let mut relight = VecDeque::new();
+
+            let level = self.get_light(channel, (x, y, z));
+
+            if level > 0 {
+                self.set_light(channel, (x, y, z), 0);
+                removal.push_back(((x, y, z), level));
+            }
+
+            self.remove_light(channel, &mut removal, &mut relight, &mut changed);
+
+            if (channel == LightChannel::Block && properties.emission > 0) {
+                self.set_light(channel, (x, y, z), properties.emission);
+                relight.push_back((x, y, z));
+            }
+
+            // Light from around flows into the space if it lets light through
+            if !properties.opaque {
+                for (ox, oy, oz) in NEIGHBOURS {
+                    relight.push_back((x + ox, y + oy, z + oz));
+                }
+            }
+
+            self.spread_light(channel, &mut relight, &mut changed);
+        }
+
+        self.mark_light_changed(changed);
+    }
+
+    // Flood fill outwards from every queued position, each step one level darker
+    fn spread_light(&mut self, channel: LightChannel, queue: &mut VecDeque<BlockPosition>, changed: &mut HashSet<chunk::ChunkPosition>) {
+        while let Some((x, y, z)) = queue.pop_front() {
+            let level = self.get_light(channel, (x, y, z));
+
+            for (ox, oy, oz) in NEIGHBOURS {
+                let neighbour = (x + ox, y + oy, z + oz);
+
+                // Full sunlight travels straight down without fading
+                let target = if (channel == LightChannel::Sky && oy == -1 && level == chunk::MAX_LIGHT) {
+                    chunk::MAX_LIGHT
+                } else {
+                    level.saturating_sub(1)
+                };
+
+                if target == 0 || self.get_light(channel, neighbour) >= target {
+                    continue;
+                }
+
+                if self.properties(self.get_block(neighbour.0, neighbour.1, neighbour.2)).opaque {
+                    continue;
+                }
+
+                if self.set_light(channel, neighbour, target) {
+                    changed.insert(chunk::to_chunk_coordinates(neighbour.0, neighbour.1, neighbour.2).0);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    // Darken everything that was lit by the queued positions. Brighter light met on the way
+    // is queued in `relight` so it can fill the gap back in
+    fn remove_light(&mut self, channel: LightChannel, queue: &mut VecDeque<(BlockPosition, u8)>, relight: &mut VecDeque<BlockPosition>, changed: &mut HashSet<chunk::ChunkPosition>) {
+        while let Some(((x, y, z), level)) = queue.pop_front() {
+            for (ox, oy, oz) in NEIGHBOURS {
+                let neighbour = (x + ox, y + oy, z + oz);
+                let neighbour_level = self.get_light(channel, neighbour);
+
+                if neighbour_level == 0 {
+                    continue;
+                }
+
+                let lit_from_here = neighbour_level < level || (channel == LightChannel::Sky && oy == -1 && level == chunk::MAX_LIGHT);
+
+                if !lit_from_here {
+                    relight.push_back(neighbour);
+                    continue;
+                }
+
+                if !self.set_light(channel, neighbour, 0) {
+                    continue;
+                }
+
+                changed.insert(chunk::to_chunk_coordinates(neighbour.0, neighbour.1, neighbour.2).0);
+                queue.push_back((neighbour, neighbour_level));
+
+                // An emitting block keeps its own light
+                let emission = self.properties(self.get_block(neighbour.0, neighbour.1, neighbour.2)).emission;
+
+                if (channel == LightChannel::Block && emission > 0) {
+                    self.set_light(channel, neighbour, emission);
+                    relight.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    // Face corners are shaded with the light around them, which can be in any chunk touching this one
+    fn mark_light_changed(&mut self, changed: HashSet<chunk::ChunkPosition>) {
+        for (cx, cy, cz) in changed {
+            for ox in -1..=1 {
+                for oy in -1..=1 {
+                    for oz in -1..=1 {
+                        self.mark_for_mesh((cx + ox, cy + oy, cz + oz));
+                    }
+                }
+            }
+        }
+    }
+
+    // Collect the chunks that changed since they were last meshed and clear their flags
+    pub fn take_chunks_to_mesh(&mut self) -> Vec<chunk::ChunkPosition> {
+        let mut positions = Vec::new();
+
+        for (position, chunk) in self.chunks.iter_mut() {
+            if chunk.needs_mesh {
+                chunk.needs_mesh = false;
+                positions.push(*position);
+            }
+        }
+
+        return positions;
+    }
+
+    // Copies of the chunks changed since they were last saved. Their flags stay set until `mark_saved`
+    pub fn chunks_to_save(&self) -> Vec<(chunk::ChunkPosition, chunk::Chunk)> {
+        self.chunks.iter()
+            .filter(|(_, chunk)| chunk.needs_save)
+            .map(|(position, chunk)| (*position, chunk.clone()))
+            .collect()
+    }
+
+    pub fn mark_saved(&mut self, positions: &[chunk::ChunkPosition]) {
+        for position in positions {
+            if let Some(chunk) = self.chunks.get_mut(position) {
+                chunk.needs_save = false;
+            }
+        }
+    }
+
+    // Build the visible faces of a chunk, skipping any face covered by a neighbouring block
+    pub fn mesh_chunk(&self, position: chunk::ChunkPosition, blocks: &HashMap<String, blockbuilder::Block>) -> ChunkMesh {
+        let mut chunk_mesh = ChunkMesh {
+            opaque: meshbuilder::MeshBuilder::new(),
+            translucent: meshbuilder::MeshBuilder::new()
+        };
+
+        let current_chunk = match self.chunks.get(&position) {
+            Some(current_chunk) => current_chunk,
+            None => return chunk_mesh
+        };
+
+        let (cx, cy, cz) = position;
+
+        // Corners are shared by up to four faces, each blended tint only needs working out once
+        let mut corner_tints: HashMap<(i32, i32), [f32; 3]> = HashMap::new();
+
+        for lx in 0..chunk::CHUNK_SIZE {
+            for ly in 0..chunk::CHUNK_SIZE {
+                for lz in 0..chunk::CHUNK_SIZE {
+                    let block_id = current_chunk.get_block(lx, ly, lz);
+
+                    if block_id == chunk::AIR {
+                        continue;
+                    }
+
+                    let block = match self.block_name(block_id).and_then(|name| blocks.get(name)) {
+                        Some(block) => block,
+                        None => continue
+                    };
+
+                    let x = cx * chunk::CHUNK_SIZE + lx;
+                    let y = cy * chunk::CHUNK_SIZE + ly;
+                    let z = cz * chunk::CHUNK_SIZE + lz;
+
+                    // Blocks occupy [x, x + 1) so the cube is shifted from its centered origin
+                    let offset = nalgebra::Point3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+
+                    let color = match self.color_jitter {
+                        true => block_jitter(x, y, z),
+                        false => models::WHITE
+                    };
+
+                    for cube in &block.cubes {
+                        for (face_type, face) in cube.faces.iter() {
+                            let (ox, oy, oz) = face_type.offset();
+
+                            // Faces show through transparent neighbours, except between two of the same block
+                            if self.is_opaque(x + ox, y + oy, z + oz) || self.get_block(x + ox, y + oy, z + oz) == block_id {
+                                continue;
+                            }
+
+                            let mut face_clone = face.clone();
+                            self.shade_face(&mut face_clone, (x, y, z), (ox, oy, oz));
+
+                            let tinted = self.colormap.is_some() && block.tinted.contains(face_type);
+
+                            for vertex in face_clone.vertices.iter_mut() {
+                                vertex.color = color;
+
+                                if tinted {
+                                    // The face is still centered, so the sign of a position says which corner it's on
+                                    let corner = (x + i32::from(vertex.position.0 > 0.0), z + i32::from(vertex.position.2 > 0.0));
+                                    let tint = *corner_tints.entry(corner).or_insert_with(|| self.biome_tint(corner.0, corner.1));
+
+                                    vertex.color = [color[0] * tint[0], color[1] * tint[1], color[2] * tint[2]];
+                                }
+                            }
+
+                            face_clone.translate_local(offset);
+
+                            if block.transparent {
+                                chunk_mesh.translucent.add_face(face_clone);
+                            } else {
+                                chunk_mesh.opaque.add_face(face_clone);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return chunk_mesh;
+    }
+
+    // Colormap colour averaged over the columns around a corner between blocks
+    fn biome_tint(&self, corner_x: i32, corner_z: i32) -> [f32; 3] {
+        let colormap = match &self.colormap {
+            Some(colormap) => colormap,
+            None => return models::WHITE
+        };
+
+        let mut total = [0.0; 3];
+        let mut count = 0.0;
+
+        for x in (corner_x - TINT_BLEND_RADIUS)..(corner_x + TINT_BLEND_RADIUS) {
+            for z in (corner_z - TINT_BLEND_RADIUS)..(corner_z + TINT_BLEND_RADIUS) {
+                let (temperature, humidity) = self.climate.get(x, z);
+                let color = colormap.get_color(temperature, humidity);
+
+                for channel in 0..3 {
+                    total[channel] += color[channel];
+                }
+
+                count += 1.0;
+            }
+        }
+
+        return [total[0] / count, total[1] / count, total[2] / count];
+    }
+
+    // Smooth light and ambient occlusion for each corner of a face. A corner touches the voxel in
+    // front of the face and the three around it in that layer, so the face has to still be centered
+    fn shade_face(&self, face: &mut cube::Face, position: BlockPosition, normal: (i32, i32, i32)) {
+        let front = offset_position(position, normal);
+        let is_opaque = |position: BlockPosition| self.is_opaque(position.0, position.1, position.2);
+
+        // Faces repeat corners between their triangles, each one only needs working out once
+        let mut shaded: Vec<(BlockPosition, [f32; 2], f32)> = Vec::with_capacity(4);
+
+        for vertex in face.vertices.iter_mut() {
+            // Which way the corner points along the two axes in the plane of the face
+            let direction = |component: f32, along_normal: i32| {
+                if along_normal != 0 {
+                    0
+                } else if component > 0.0 {
+                    1
+                } else {
+                    -1
+                }
+            };
+
+            let corner = (direction(vertex.position.0, normal.0), direction(vertex.position.1, normal.1), direction(vertex.position.2, normal.2));
+
+            if let Some((_, light, occlusion)) = shaded.iter().find(|(shaded_corner, _, _)| *shaded_corner == corner) {
+                vertex.light = *light;
+                vertex.occlusion = *occlusion;
+                continue;
+            }
+
+            let (first, second) = match corner {
+                (0, y, z) => ((0, y, 0), (0, 0, z)),
+                (x, 0, z) => ((x, 0, 0), (0, 0, z)),
+                (x, y, _) => ((x, 0, 0), (0, y, 0))
+            };
+
+            let (first, second, diagonal) = (offset_position(front, first), offset_position(front, second), offset_position(front, corner));
+            let (first_opaque, second_opaque, diagonal_opaque) = (is_opaque(first), is_opaque(second), is_opaque(diagonal));
+
+            let enclosed = first_opaque && second_opaque;
+
+            let occlusion = if enclosed {
+                0
+            } else {
+                3 - (first_opaque as u8 + second_opaque as u8 + diagonal_opaque as u8)
+            };
+
+            // Average over the voxels light can be in. The diagonal can't be seen past two solid sides
+            let samples = [
+                (front, true),
+                (first, !first_opaque),
+                (second, !second_opaque),
+                (diagonal, !(diagonal_opaque || enclosed))
+            ];
+
+            let (mut sky, mut block, mut count) = (0, 0, 0);
+
+            for (sample, lit) in samples {
+                if lit {
+                    let (sample_sky, sample_block) = self.get_light_levels(sample);
+
+                    sky += sample_sky as u32;
+                    block += sample_block as u32;
+                    count += 1;
+                }
+            }
+
+            let total = (count * chunk::MAX_LIGHT as u32) as f32;
+
+            vertex.light = [sky as f32 / total, block as f32 / total];
+            vertex.occlusion = occlusion as f32 / 3.0;
+
+            shaded.push((corner, vertex.light, vertex.occlusion));
+        }
+
+        face.split_along_brighter_diagonal();
+    }
+}
+
+fn offset_position(position: BlockPosition, offset: (i32, i32, i32)) -> BlockPosition {
+    (position.0 + offset.0, position.1 + offset.1, position.2 + offset.2)
+}
+
+// Brightness picked from a hash of the position, so a block looks the same every time it's meshed
+fn block_jitter(x: i32, y: i32, z: i32) -> [f32; 3] {
+    let mut hash = (x as u32).wrapping_mul(73856093) ^ (y as u32).wrapping_mul(19349663) ^ (z as u32).wrapping_mul(83492791);
+
+    // Mix the bits so neighbouring blocks don't get similar values
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+
+    let brightness = 1.0 - JITTER_STRENGTH * (hash as f32 / u32::MAX as f32);
+
+    return [brightness; 3];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single chunk with a dirt floor at y = 0, a dirt roof at y = 5 and a lamp block defined
+    fn roofed_world() -> World {
+        let mut blocks = HashMap::new();
+
+        for (name, light) in [("dirt", 0), ("lamp", 15)] {
+            blockbuilder::BlockBuilder::new()
+                .set_name(name)
+                .set_light(light)
+                .add_cube(cube::Cube::new())
+                .build(Some(&mut blocks));
+        }
+
+        let mut test_world = World::new(0);
+        test_world.set_block_properties(&blocks);
+
+        let dirt = test_world.block_id("dirt");
+
+        for x in 0..chunk::CHUNK_SIZE {
+            for z in 0..chunk::CHUNK_SIZE {
+                test_world.set_block(x, 0, z, dirt);
+                test_world.set_block(x, 5, z, dirt);
+            }
+        }
+
+        test_world.update_light();
+
+        test_world
+    }
+
+    // Occlusion of every upward facing vertex at a corner
+    fn top_occlusion(mesh: &meshbuilder::MeshBuilder, x: f32, y: f32, z: f32) -> Vec<f32> {
+        mesh.vertices.iter()
+            .filter(|vertex| vertex.normal == (0.0, 1.0, 0.0) && vertex.position == (x, y, z))
+            .map(|vertex| vertex.occlusion)
+            .collect()
+    }
+
+    #[test]
+    fn roof_keeps_sunlight_out() {
+        let test_world = roofed_world();
+
+        assert_eq!(test_world.get_sky_light(8, 6, 8), chunk::MAX_LIGHT);
+        assert_eq!(test_world.get_sky_light(8, 15, 8), chunk::MAX_LIGHT);
+        assert_eq!(test_world.get_sky_light(8, 3, 8), 0);
+    }
+
+    #[test]
+    fn sunlight_follows_a_hole_and_goes_when_it_is_filled() {
+        let mut test_world = roofed_world();
+        let dirt = test_world.block_id("dirt");
+
+        test_world.set_block(8, 5, 8, chunk::AIR);
+
+        // Straight down through the hole stays at full strength, then fades sideways
+        assert_eq!(test_world.get_sky_light(8, 1, 8), chunk::MAX_LIGHT);
+        assert_eq!(test_world.get_sky_light(10, 1, 8), chunk::MAX_LIGHT - 2);
+        assert_eq!(test_world.get_sky_light(8, 4, 13), chunk::MAX_LIGHT - 5);
+
+        test_world.set_block(8, 5, 8, dirt);
+
+        for position in [(8, 1, 8), (10, 1, 8), (8, 4, 13)] {
+            assert_eq!(test_world.get_sky_light(position.0, position.1, position.2), 0);
+        }
+    }
+
+    #[test]
+    fn block_light_spreads_and_is_removed() {
+        let mut test_world = roofed_world();
+        let lamp = test_world.block_id("lamp");
+
+        test_world.set_block(8, 2, 8, lamp);
+
+        assert_eq!(test_world.get_block_light(8, 2, 8), 15);
+        assert_eq!(test_world.get_block_light(11, 2, 8), 12);
+        assert_eq!(test_world.get_block_light(11, 3, 9), 10);
+        // Light goes around the roof, not through it
+        assert_eq!(test_world.get_block_light(8, 6, 8), 0);
+
+        test_world.set_block(8, 2, 8, chunk::AIR);
+
+        assert_eq!(test_world.get_block_light(8, 2, 8), 0);
+        assert_eq!(test_world.get_block_light(11, 2, 8), 0);
+    }
+
+    #[test]
+    fn removing_one_lamp_keeps_the_other_lit() {
+        let mut test_world = roofed_world();
+        let lamp = test_world.block_id("lamp");
+
+        test_world.set_block(2, 2, 8, lamp);
+        test_world.set_block(10, 2, 8, lamp);
+        test_world.set_block(2, 2, 8, chunk::AIR);
+
+        assert_eq!(test_world.get_block_light(10, 2, 8), 15);
+        assert_eq!(test_world.get_block_light(6, 2, 8), 11);
+        assert_eq!(test_world.get_block_light(2, 2, 8), 7);
+    }
+
+    #[test]
+    fn new_columns_pick_up_light_from_their_neighbours() {
+        let mut test_world = roofed_world();
+        let lamp = test_world.block_id("lamp");
+        let dirt = test_world.block_id("dirt");
+
+        test_world.set_block(15, 2, 8, lamp);
+
+        // The next column over is created dark and lit on the next update
+        test_world.set_block(chunk::CHUNK_SIZE + 8, 0, 8, dirt);
+
+        assert_eq!(test_world.get_block_light(chunk::CHUNK_SIZE + 2, 2, 8), 0);
+
+        test_world.update_light();
+
+        assert_eq!(test_world.get_block_light(chunk::CHUNK_SIZE + 2, 2, 8), 12);
+        assert_eq!(test_world.get_sky_light(chunk::CHUNK_SIZE + 2, 2, 8), chunk::MAX_LIGHT);
+    }
+
+    #[test]
+    fn corners_next_to_blocks_are_occluded() {
+        let mut test_world = roofed_world();
+        let dirt = test_world.block_id("dirt");
+
+        test_world.set_block(8, 1, 8, dirt);
+        test_world.set_block(9, 1, 9, dirt);
+        test_world.update_light();
+
+        let mut blocks = HashMap::new();
+        blockbuilder::BlockBuilder::new().set_name("dirt").add_cube(cube::Cube::new()).build(Some(&mut blocks));
+
+        let mesh = test_world.mesh_chunk((0, 0, 0), &blocks).opaque;
+
+        // Every face sharing a corner agrees on it
+        let open = top_occlusion(&mesh, 12.0, 1.0, 12.0);
+        let one_side = top_occlusion(&mesh, 8.0, 1.0, 8.0);
+        let two_sides = top_occlusion(&mesh, 9.0, 1.0, 9.0);
+
+        assert!(!open.is_empty() && open.iter().all(|occlusion| *occlusion == 1.0));
+        assert!(!one_side.is_empty() && one_side.iter().all(|occlusion| (occlusion - 2.0 / 3.0).abs() < 1e-6));
+        assert!(!two_sides.is_empty() && two_sides.iter().all(|occlusion| *occlusion == 0.0));
+    }
+
+    #[test]
+    fn faces_show_through_transparent_blocks() {
+        let mut blocks = HashMap::new();
+
+        for (name, transparent) in [("dirt", false), ("glass", true)] {
+            blockbuilder::BlockBuilder::new()
+                .set_name(name)
+                .set_transparent(transparent)
+                .add_cube(cube::Cube::new())
+                .build(Some(&mut blocks));
+        }
+
+        let mut test_world = World::new(0);
+        test_world.set_block_properties(&blocks);
+
+        let dirt = test_world.block_id("dirt");
+        let glass = test_world.block_id("glass");
+
+        test_world.set_block(4, 4, 4, dirt);
+        test_world.set_block(5, 4, 4, glass);
+        test_world.set_block(6, 4, 4, glass);
+
+        let mesh = test_world.mesh_chunk((0, 0, 0), &blocks);
+
+        // All of the dirt, and the glass minus the faces against the dirt and against each other.
+        // The glass is drawn separately, blended over the dirt
+        assert_eq!(mesh.opaque.vertices.len() / 6, 6);
+        assert_eq!(mesh.translucent.vertices.len() / 6, 4 + 5);
+    }
+
+    #[test]
+    fn color_jitter_is_per_block_and_stable() {
+        let mut test_world = roofed_world();
+
+        let mut blocks = HashMap::new();
+        blockbuilder::BlockBuilder::new().set_name("dirt").add_cube(cube::Cube::new()).build(Some(&mut blocks));
+
+        let plain = test_world.mesh_chunk((0, 0, 0), &blocks).opaque;
+        assert!(plain.vertices.iter().all(|vertex| vertex.color == models::WHITE));
+
+        test_world.set_color_jitter(true);
+
+        let jittered = test_world.mesh_chunk((0, 0, 0), &blocks).opaque;
+        let colors: Vec<[f32; 3]> = jittered.vertices.iter().map(|vertex| vertex.color).collect();
+
+        // Faces of one block share a colour, different blocks mostly don't
+        for face in jittered.vertices.chunks(6) {
+            assert!(face.iter().all(|vertex| vertex.color == face[0].color));
+        }
+
+        assert!(colors.iter().any(|color| *color != colors[0]));
+        assert!(colors.iter().all(|color| color[0] >= 1.0 - JITTER_STRENGTH && color[0] <= 1.0));
+        assert_eq!(colors, test_world.mesh_chunk((0, 0, 0), &blocks).opaque.vertices.iter().map(|vertex| vertex.color).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tinted_faces_blend_at_shared_corners() {
+        let mut test_world = roofed_world();
+
+        // Every cell different, so neighbouring columns of the world pick different colours
+        let colormap = image::RgbaImage::from_fn(16, 16, |x, y| image::Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255]));
+        test_world.set_colormap(biome::Colormap::new(&colormap));
+
+        let mut blocks = HashMap::new();
+        blockbuilder::BlockBuilder::new().set_name("dirt").set_tinted(vec![cube::Faces::Top]).add_cube(cube::Cube::new()).build(Some(&mut blocks));
+
+        let mesh = test_world.mesh_chunk((0, 0, 0), &blocks).opaque;
+
+        for vertex in mesh.vertices.iter() {
+            if vertex.normal != (0.0, 1.0, 0.0) {
+                assert_eq!(vertex.color, models::WHITE);
+                continue;
+            }
+
+            // Faces meeting at a corner agree on its colour, so there are no seams between blocks
+            let shared = top_colors(&mesh, vertex.position);
+
+            assert!(vertex.color != models::WHITE);
+            assert!(shared.iter().all(|color| *color == vertex.color));
+        }
+    }
+
+    fn top_colors(mesh: &meshbuilder::MeshBuilder, position: (f32, f32, f32)) -> Vec<[f32; 3]> {
+        mesh.vertices.iter()
+            .filter(|vertex| vertex.normal == (0.0, 1.0, 0.0) && vertex.position == position)
+            .map(|vertex| vertex.color)
+            .collect()
+    }
+}